@@ -1,7 +1,12 @@
+use anyhow::{Context, Result};
 use nom::character::complete::char;
 use nom::combinator::map;
 use nom::sequence::tuple;
 use nom::IResult;
+use pico_args::Arguments;
+
+use crate::image::Bitmap;
+use crate::ocr;
 
 const INPUT: &str = include_str!("../inputs/day_10_input");
 
@@ -62,93 +67,131 @@ impl Point {
     }
 }
 
-pub fn solve() {
-    let mut points = INPUT
+impl Point {
+    fn position_at(&self, time: i64) -> (i64, i64) {
+        (
+            self.position.0 as i64 + self.velocity.0 as i64 * time,
+            self.position.1 as i64 + self.velocity.1 as i64 * time,
+        )
+    }
+}
+
+fn get_points() -> Vec<Point> {
+    INPUT
         .lines()
         .map(|i| Point::parse(i).unwrap().1)
-        .collect::<Vec<_>>();
-
-    let mut min_delta = i32::MAX;
-    let mut min_step = 0;
+        .collect::<Vec<_>>()
+}
 
-    for i in 0..80000 {
-        advance_points(&mut points);
-        let (min, max) = find_extrema(&points);
+/// Finds time when bounding box of all points has the smallest area
+fn convergence_time(points: &[Point]) -> i64 {
+    let count = points.len() as f64;
+    let mean = |f: fn(&Point) -> i32| points.iter().map(|point| f(point) as f64).sum::<f64>() / count;
+    let (pos_x, pos_y) = (mean(|p| p.position.0), mean(|p| p.position.1));
+    let (vel_x, vel_y) = (mean(|p| p.velocity.0), mean(|p| p.velocity.1));
 
-        let delta = (max.0 - min.0 + 1) + (max.1 - min.1 + 1);
-        if delta < min_delta {
-            min_delta = delta;
-            min_step = i;
-        }
+    // least squares estimate of time when points are closest to their centroid
+    let (mut along, mut speed) = (0.0, 0.0);
+    for point in points {
+        let (dp_x, dp_y) = (point.position.0 as f64 - pos_x, point.position.1 as f64 - pos_y);
+        let (dv_x, dv_y) = (point.velocity.0 as f64 - vel_x, point.velocity.1 as f64 - vel_y);
+        along += dp_x * dv_x + dp_y * dv_y;
+        speed += dv_x * dv_x + dv_y * dv_y;
+    }
+    let estimate = if speed == 0.0 { 0.0 } else { -along / speed };
+
+    // the estimate is close, but smallest area doesn't have to be exactly there
+    let area = |time| {
+        let positions = points.iter().map(|point| point.position_at(time)).collect::<Vec<_>>();
+        let (min, max) = find_extrema(&positions);
+        (max.0 - min.0 + 1) * (max.1 - min.1 + 1)
+    };
+
+    let mut time = estimate.round().max(0.0) as i64;
+    while time > 0 && area(time - 1) < area(time) {
+        time -= 1;
     }
+    while area(time + 1) < area(time) {
+        time += 1;
+    }
+    time
+}
 
-    let mut points = INPUT
-        .lines()
-        .map(|i| Point::parse(i).unwrap().1)
-        .collect::<Vec<_>>();
+/// Draws points at the time they are closest together
+fn draw_message(points: &[Point]) -> Bitmap {
+    let time = convergence_time(points);
+    let positions = points.iter().map(|point| point.position_at(time)).collect::<Vec<_>>();
+
+    let (min, max) = find_extrema(&positions);
 
-    for _ in 0..=min_step {
-        advance_points(&mut points);
+    let mut message = Bitmap::new((max.0 - min.0 + 1) as usize, (max.1 - min.1 + 1) as usize);
+    for position in &positions {
+        message.set((position.0 - min.0) as usize, (position.1 - min.1) as usize, true);
     }
+    message
+}
 
-    let (min, max) = find_extrema(&points);
+#[derive(Debug)]
+pub struct Options {
+    export: Option<String>,
+    scale: usize,
+}
 
-    for x in min.1..=max.1 {
-        for y in min.0..=max.0 {
-            let mut contains = false;
-            for point in &points {
-                if point.position == (y, x) {
-                    contains = true;
-                    break;
-                }
-            }
-            if contains {
-                print!("#");
-            } else {
-                print!(" ");
-            }
-        }
-        print!("\n");
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        Ok(Self {
+            export: args
+                .opt_value_from_str("--export")
+                .context("Did not get valid --export parameter value")?,
+            scale: args
+                .opt_value_from_str("--scale")
+                .context("Did not get valid --scale parameter value")?
+                .unwrap_or(4),
+        })
     }
 }
 
-fn advance_points(points: &mut [Point]) {
-    for point in points {
-        point.position.0 += point.velocity.0;
-        point.position.1 += point.velocity.1;
+pub fn run(options: &Options) -> Result<()> {
+    solve(options)?;
+    solve_extra();
+
+    Ok(())
+}
+
+fn solve(options: &Options) -> Result<()> {
+    let points = get_points();
+
+    let message = draw_message(&points);
+
+    print!("{}", message.render('#', ' '));
+
+    match ocr::read_text(&message) {
+        Ok(text) => println!("Message: {}", text),
+        Err(e) => println!("{:#}", e),
     }
+
+    if let Some(path) = &options.export {
+        message.save_pbm(path, options.scale)?;
+        println!("Message saved to {}", path);
+    }
+
+    Ok(())
 }
 
-fn find_extrema(points: &[Point]) -> ((i32, i32), (i32, i32)) {
-    let mut min = (i32::MAX, i32::MAX);
-    let mut max = (i32::MIN, i32::MIN);
-    for point in points {
-        min.0 = min.0.min(point.position.0);
-        max.0 = max.0.max(point.position.0);
-        min.1 = min.1.min(point.position.1);
-        max.1 = max.1.max(point.position.1);
+fn find_extrema(positions: &[(i64, i64)]) -> ((i64, i64), (i64, i64)) {
+    let mut min = (i64::MAX, i64::MAX);
+    let mut max = (i64::MIN, i64::MIN);
+    for position in positions {
+        min.0 = min.0.min(position.0);
+        max.0 = max.0.max(position.0);
+        min.1 = min.1.min(position.1);
+        max.1 = max.1.max(position.1);
     }
     (min, max)
 }
 
-pub fn solve_extra() {
-    let mut points = INPUT
-        .lines()
-        .map(|i| Point::parse(i).unwrap().1)
-        .collect::<Vec<_>>();
-
-    let mut min_delta = i32::MAX;
-    let mut min_step = 0;
+fn solve_extra() {
+    let points = get_points();
 
-    for i in 0..80000 {
-        advance_points(&mut points);
-        let (min, max) = find_extrema(&points);
-
-        let delta = (max.0 - min.0 + 1) + (max.1 - min.1 + 1);
-        if delta < min_delta {
-            min_delta = delta;
-            min_step = i;
-        }
-    }
-    println!("Min steps: {}", min_step + 1);
+    println!("Min steps: {}", convergence_time(&points));
 }
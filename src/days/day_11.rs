@@ -1,82 +1,234 @@
+use std::ops::RangeInclusive;
+use std::thread;
+
+use anyhow::{Context, Result};
+use pico_args::Arguments;
+
 const INPUT: i32 = 3463;
 
-pub fn solve() {
-    let powers = (0..(300 * 300))
-        .map(|a| pow_from_index(a))
-        .collect::<Vec<_>>();
+#[derive(Debug, Copy, Clone)]
+struct Square {
+    x: i32,
+    y: i32,
+    size: i32,
+    power: i32,
+}
 
-    let mut max_power = i32::MIN;
-    let mut max_x = 1;
-    let mut max_y = 1;
+/// Grid of fuel cells with 1-based coordinates, backed by summed-area table
+#[derive(Debug)]
+struct FuelGrid {
+    width: i32,
+    height: i32,
+    /// entry at `(x, y)` holds power of all cells from `(1, 1)` to `(x, y)` inclusive
+    sums: Vec<i32>,
+}
 
-    let coord_to_index = |x, y| ((x - 1) + (y - 1) * 300) as usize;
+impl FuelGrid {
+    fn new(serial: i32, width: i32, height: i32) -> Self {
+        let mut grid = Self {
+            width,
+            height,
+            sums: vec![0; ((width + 1) * (height + 1)) as usize],
+        };
+        for y in 1..=height {
+            for x in 1..=width {
+                let left = grid.sums[grid.index(x - 1, y)];
+                let top = grid.sums[grid.index(x, y - 1)];
+                let diagonal = grid.sums[grid.index(x - 1, y - 1)];
 
-    for start_x in 1..299 {
-        for start_y in 1..299 {
-            let mut power = 0;
-            for x in start_x..(start_x + 3) {
-                for y in start_y..(start_y + 3) {
-                    power += powers[coord_to_index(x, y)];
-                }
+                let index = grid.index(x, y);
+                grid.sums[index] = cell_power(serial, x, y) + left + top - diagonal;
             }
+        }
+        grid
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (x + y * (self.width + 1)) as usize
+    }
 
-            if power > max_power {
-                max_power = power;
-                max_x = start_x;
-                max_y = start_y;
+    /// Power of square with top left corner on `(x, y)`, `None` if it doesn't fit in the grid
+    fn square_power(&self, x: i32, y: i32, size: i32) -> Option<i32> {
+        let (end_x, end_y) = (x + size - 1, y + size - 1);
+        if x < 1 || y < 1 || size < 1 || end_x > self.width || end_y > self.height {
+            return None;
+        }
+
+        Some(
+            self.sums[self.index(end_x, end_y)]
+                - self.sums[self.index(x - 1, end_y)]
+                - self.sums[self.index(end_x, y - 1)]
+                + self.sums[self.index(x - 1, y - 1)],
+        )
+    }
+
+    /// On ties the square with lowest `x`, then lowest `y` wins
+    fn best_square(&self, size: i32) -> Option<Square> {
+        if size < 1 {
+            return None;
+        }
+
+        let mut best: Option<Square> = None;
+
+        for x in 1..=(self.width - size + 1) {
+            for y in 1..=(self.height - size + 1) {
+                let power = self.square_power(x, y, size).unwrap();
+                if best.is_none_or(|best| power > best.power) {
+                    best = Some(Square { x, y, size, power });
+                }
             }
         }
+
+        best
     }
 
-    println!("Max power {} on {}, {}", max_power, max_x, max_y);
+    /// Splits sizes between `threads` workers, on ties the smallest square wins no matter which worker found it
+    fn best_square_in(&self, sizes: RangeInclusive<i32>, threads: usize) -> Option<Square> {
+        let sizes = sizes.collect::<Vec<_>>();
+        let threads = threads.clamp(1, sizes.len().max(1));
+
+        thread::scope(|scope| {
+            // sizes are dealt out round robin, bigger squares have fewer positions to check
+            let workers = (0..threads)
+                .map(|worker| {
+                    let sizes = sizes.iter().skip(worker).step_by(threads);
+                    scope.spawn(move || {
+                        sizes
+                            .filter_map(|size| self.best_square(*size))
+                            .fold(None, better_square)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .filter_map(|worker| worker.join().unwrap())
+                .fold(None, better_square)
+        })
+    }
 }
 
-fn pow_from_index(index: i32) -> i32 {
-    let x = (index % 300) + 1;
-    let y = index / 300 + 1;
+fn better_square(best: Option<Square>, square: Square) -> Option<Square> {
+    match best {
+        Some(best) if (best.power, -best.size) >= (square.power, -square.size) => Some(best),
+        _ => Some(square),
+    }
+}
 
+fn cell_power(serial: i32, x: i32, y: i32) -> i32 {
     let rack = x + 10;
     let mut power = rack * y;
-    power += INPUT;
+    power += serial;
     power *= rack;
     power = (power / 100) % 10;
     power -= 5;
     power
 }
 
-pub fn solve_extra() {
-    let powers = (0..(300 * 300))
-        .map(|a| pow_from_index(a))
-        .collect::<Vec<_>>();
-
-    let mut max_power = i32::MIN;
-    let mut max_x = 1;
-    let mut max_y = 1;
-    let mut max_size = 1;
-
-    let coord_to_index = |x, y| ((x - 1) + (y - 1) * 300) as usize;
-    for size in 1..300 {
-        for start_x in 1..(300 - size) {
-            for start_y in 1..(300 - size) {
-                let mut power = 0;
-                for x in start_x..(start_x + size) {
-                    for y in start_y..(start_y + size) {
-                        power += powers[coord_to_index(x, y)];
-                    }
-                }
+#[derive(Debug)]
+pub struct Options {
+    serial: i32,
+    width: i32,
+    height: i32,
+    /// square size searched for in first part
+    size: i32,
+    /// square sizes searched for in second part
+    sizes: RangeInclusive<i32>,
+    /// squares to print power of, as `(x, y, size)`
+    squares: Vec<(i32, i32, i32)>,
+    threads: usize,
+}
 
-                if power > max_power {
-                    max_power = power;
-                    max_x = start_x;
-                    max_y = start_y;
-                    max_size = size;
-                }
-            }
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        let width = args
+            .opt_value_from_str("--width")
+            .context("Did not get valid --width parameter value")?
+            .unwrap_or(300);
+        let height = args
+            .opt_value_from_str("--height")
+            .context("Did not get valid --height parameter value")?
+            .unwrap_or(300);
+        if width < 1 || height < 1 {
+            anyhow::bail!("--width and --height have to be at least 1");
+        }
+
+        let min_size = args
+            .opt_value_from_str("--min-size")
+            .context("Did not get valid --min-size parameter value")?
+            .unwrap_or(1);
+        let max_size = args
+            .opt_value_from_str("--max-size")
+            .context("Did not get valid --max-size parameter value")?
+            .unwrap_or_else(|| i32::min(width, height));
+        if min_size > max_size {
+            anyhow::bail!("--min-size can't be bigger than --max-size");
         }
+
+        Ok(Self {
+            serial: args
+                .opt_value_from_str("--serial")
+                .context("Did not get valid --serial parameter value")?
+                .unwrap_or(INPUT),
+            width,
+            height,
+            size: args
+                .opt_value_from_str("--size")
+                .context("Did not get valid --size parameter value")?
+                .unwrap_or(3),
+            sizes: min_size..=max_size,
+            squares: args
+                .values_from_fn("--square", parse_square)
+                .context("Did not get valid --square parameter value, expected `x,y,size`")?,
+            threads: match args
+                .opt_value_from_str("--threads")
+                .context("Did not get valid --threads parameter value")?
+            {
+                Some(threads) => threads,
+                None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+            },
+        })
+    }
+}
+
+fn parse_square(i: &str) -> Result<(i32, i32, i32)> {
+    let nums = i
+        .split(',')
+        .map(|num| num.trim().parse())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match nums[..] {
+        [x, y, size] => Ok((x, y, size)),
+        _ => anyhow::bail!("expected 3 numbers, got {}", nums.len()),
     }
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    let grid = FuelGrid::new(options.serial, options.width, options.height);
+
+    solve(&grid, options);
+    solve_extra(&grid, options);
 
-    println!(
-        "Max power {} on {}, {} size {}",
-        max_power, max_x, max_y, max_size
-    );
+    for &(x, y, size) in &options.squares {
+        let power = grid
+            .square_power(x, y, size)
+            .with_context(|| format!("Square on {}, {} size {} does not fit in the grid", x, y, size))?;
+        println!("Power {} on {}, {} size {}", power, x, y, size);
+    }
+
+    Ok(())
+}
+
+fn solve(grid: &FuelGrid, options: &Options) {
+    match grid.best_square(options.size) {
+        Some(Square { x, y, power, .. }) => println!("Max power {} on {}, {}", power, x, y),
+        None => println!("No square of size {} fits in the grid", options.size),
+    }
+}
+
+fn solve_extra(grid: &FuelGrid, options: &Options) {
+    match grid.best_square_in(options.sizes.clone(), options.threads) {
+        Some(Square { x, y, size, power }) => println!("Max power {} on {}, {} size {}", power, x, y, size),
+        None => println!("No square of size {:?} fits in the grid", options.sizes),
+    }
 }
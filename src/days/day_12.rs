@@ -1,21 +1,20 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Instant;
 
+use anyhow::{Context, Result};
 use nom::bytes::complete::tag;
 use nom::IResult;
+use pico_args::Arguments;
 
-const INPUT: &str = include_str!("../inputs/day_12_input");
-
-fn parse_rule(i: &str) -> IResult<&str, ([bool; 5], bool)> {
-    use nom::multi::many_m_n;
+use crate::image::Bitmap;
 
-    let (i, pots) = many_m_n(5, 5, pot)(i)?;
+const INPUT: &str = include_str!("../inputs/day_12_input");
 
-    // i don't like this
-    let mut pattern = [false; 5];
-    for i in 0..5 {
-        pattern[i] = pots[i];
-    }
+fn parse_rule(i: &str) -> IResult<&str, (Vec<bool>, bool)> {
+    use nom::multi::many1;
 
+    let (i, pattern) = many1(pot)(i)?;
     let (i, _) = tag(" => ")(i)?;
     let (i, result) = pot(i)?;
 
@@ -42,48 +41,202 @@ fn pot(i: &str) -> IResult<&str, bool> {
     map(one_of(".#"), |a| a == '#')(i)
 }
 
-pub fn solve() {
-    let (i, pots_input) = parse_pots(INPUT).unwrap();
-    let rules = i
-        .lines()
-        .skip(1)
-        .map(|i| parse_rule(i).unwrap().1)
-        .collect::<HashMap<_, _>>();
+/// Rule results for every window of `2 * radius + 1` pots, indexed by the window read as bits with the leftmost pot
+/// on top
+#[derive(Debug)]
+struct RuleTable {
+    radius: usize,
+    table: Vec<bool>,
+}
+
+impl RuleTable {
+    /// widest pattern whose table still fits in memory comfortably
+    const MAX_WIDTH: usize = 25;
 
-    assert_eq!(32, rules.len(), "Invalid number of rules, should be 32");
-
-    let mut pots = vec![false; 800];
-    pots.splice(400..400, pots_input);
-
-    for _ in 0..20 {
-        let p_clone = pots.clone();
-        for x in 2..898 {
-            let key = [
-                p_clone[x - 2],
-                p_clone[x - 1],
-                p_clone[x],
-                p_clone[x + 1],
-                p_clone[x + 2],
-            ];
-            pots[x] = rules[&key];
+    /// Builds table from pattern rules, patterns without a rule produce an empty pot
+    fn new(rules: &HashMap<Vec<bool>, bool>) -> Result<Self> {
+        let width = rules.keys().next().map_or(1, |pattern| pattern.len());
+        if width.is_multiple_of(2) {
+            anyhow::bail!("Rule patterns must have odd length, got {}", width);
         }
+        if width > Self::MAX_WIDTH {
+            anyhow::bail!(
+                "Rule patterns can be at most {} pots long, got {}",
+                Self::MAX_WIDTH,
+                width
+            );
+        }
+
+        let mut table = vec![false; 1 << width];
+        for (pattern, result) in rules {
+            if pattern.len() != width {
+                anyhow::bail!("All rule patterns must have the same length");
+            }
+            let index = pattern.iter().fold(0, |acc, pot| (acc << 1) | *pot as usize);
+            table[index] = *result;
+        }
+
+        Self::checked(width / 2, table)
+    }
+
+    /// Builds radius 1 table from Wolfram code, where bit `n` is the result for window `n`
+    fn wolfram(code: u8) -> Result<Self> {
+        let table = (0..8).map(|window| (code >> window) & 1 == 1).collect();
+        Self::checked(1, table)
     }
 
-    let sum = pot_sum(&pots, 400);
+    fn checked(radius: usize, table: Vec<bool>) -> Result<Self> {
+        if table[0] {
+            anyhow::bail!("Empty pots can't grow plants, there would be infinitely many of them");
+        }
+        Ok(Self { radius, table })
+    }
+}
 
-    println!("Pot sum: {}", sum);
+#[derive(Debug, Clone, Default)]
+struct Tape {
+    /// pots packed in bits, pot `n` lives at bit `n % 64` of word `n / 64`
+    words: Vec<u64>,
+    len: usize,
+    /// number of the pot stored in the lowest bit of `words[0]`
+    offset: i64,
 }
 
-fn pot_sum(pots: &[bool], center: i32) -> i32 {
-    pots.iter().enumerate().fold(0, |mut acc, (i, pot)| {
-        if *pot {
-            acc += i as i32 - center;
+impl Tape {
+    fn new(pots: &[bool]) -> Self {
+        let mut tape = Self {
+            words: vec![0; pots.len().div_ceil(64)],
+            len: pots.len(),
+            offset: 0,
+        };
+        for (i, _) in pots.iter().enumerate().filter(|(_, pot)| **pot) {
+            tape.words[i / 64] |= 1 << (i % 64);
         }
-        acc
-    })
+        tape.trim();
+        tape
+    }
+
+    fn get(&self, index: usize) -> u64 {
+        match self.words.get(index / 64) {
+            Some(word) => (word >> (index % 64)) & 1,
+            None => 0,
+        }
+    }
+
+    /// Cuts off empty pots on both ends, so the tape spans from leftmost to rightmost plant
+    fn trim(&mut self) {
+        let first_word = match self.words.iter().position(|a| *a != 0) {
+            Some(first_word) => first_word,
+            None => {
+                self.words.clear();
+                self.len = 0;
+                self.offset = 0;
+                return;
+            }
+        };
+        let last_word = self.words.iter().rposition(|a| *a != 0).unwrap();
+
+        let first = first_word * 64 + self.words[first_word].trailing_zeros() as usize;
+        let last = last_word * 64 + 63 - self.words[last_word].leading_zeros() as usize;
+
+        let (word_shift, bit_shift) = (first / 64, first % 64);
+        for i in 0..(self.words.len() - word_shift) {
+            let mut word = self.words[i + word_shift] >> bit_shift;
+            if bit_shift != 0 {
+                if let Some(next) = self.words.get(i + word_shift + 1) {
+                    word |= next << (64 - bit_shift);
+                }
+            }
+            self.words[i] = word;
+        }
+
+        self.len = last - first + 1;
+        self.words.truncate(self.len.div_ceil(64));
+        self.offset += first as i64;
+    }
+
+    /// Writes next generation into `next`, reusing its allocation
+    fn step_into(&self, rules: &RuleTable, next: &mut Tape) {
+        // plants can only appear up to `radius` pots away from the outermost ones
+        next.len = self.len + 2 * rules.radius;
+        next.offset = self.offset - rules.radius as i64;
+        next.words.clear();
+        next.words.resize(next.len.div_ceil(64), 0);
+
+        let mask = rules.table.len() - 1;
+        let mut window = 0;
+        for i in 0..next.len {
+            // new pot `i` is above old pot `i - radius`, so the window needs old pots up to `i`
+            window = ((window << 1) | self.get(i) as usize) & mask;
+            next.words[i / 64] |= (rules.table[window] as u64) << (i % 64);
+        }
+
+        next.trim();
+    }
+
+    fn plant_count(&self) -> u32 {
+        self.words.iter().map(|word| word.count_ones()).sum()
+    }
+
+    fn pot_sum(&self) -> i64 {
+        (0..self.len)
+            .filter(|i| self.get(*i) == 1)
+            .map(|i| i as i64 + self.offset)
+            .sum()
+    }
+}
+
+/// Previous automaton with one `bool` per pot and a `HashMap` of rules, kept to compare against in `bench`
+#[derive(Debug)]
+struct UnpackedTape {
+    pots: Vec<bool>,
+    offset: i64,
+}
+
+impl UnpackedTape {
+    fn get(&self, index: i64) -> bool {
+        if index < 0 || index >= self.pots.len() as i64 {
+            false
+        } else {
+            self.pots[index as usize]
+        }
+    }
+
+    fn step(&self, rules: &HashMap<[bool; 5], bool>) -> Self {
+        // missing rules produce an empty pot, same as in the packed tape
+        let pots = (-2..(self.pots.len() as i64 + 2))
+            .map(|x| {
+                let key = [
+                    self.get(x - 2),
+                    self.get(x - 1),
+                    self.get(x),
+                    self.get(x + 1),
+                    self.get(x + 2),
+                ];
+                rules.get(&key).copied().unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+
+        let first = pots.iter().position(|a| *a).unwrap_or(0);
+        let last = pots.iter().rposition(|a| *a).map_or(0, |a| a + 1);
+
+        Self {
+            pots: pots[first..last.max(first)].to_vec(),
+            offset: self.offset - 2 + first as i64,
+        }
+    }
+
+    fn pot_sum(&self) -> i64 {
+        self.pots
+            .iter()
+            .enumerate()
+            .filter(|(_, pot)| **pot)
+            .map(|(i, _)| i as i64 + self.offset)
+            .sum()
+    }
 }
 
-pub fn solve_extra() {
+fn parse_input() -> (Vec<bool>, HashMap<Vec<bool>, bool>) {
     let (i, pots_input) = parse_pots(INPUT).unwrap();
     let rules = i
         .lines()
@@ -91,54 +244,228 @@ pub fn solve_extra() {
         .map(|i| parse_rule(i).unwrap().1)
         .collect::<HashMap<_, _>>();
 
-    assert_eq!(32, rules.len(), "Invalid number of rules, should be 32");
+    (pots_input, rules)
+}
+
+#[derive(Debug)]
+struct Cycle {
+    start: u64,
+    period: u64,
+    /// how many pots the pattern moves by every period
+    shift: i64,
+}
+
+/// Runs the automaton to `target` generation, skipping ahead once the trimmed pattern repeats
+fn extrapolate(mut tape: Tape, rules: &RuleTable, target: u64) -> (Tape, Option<Cycle>) {
+    let mut seen = HashMap::new();
+    let mut next = Tape::default();
 
-    let mut pots = vec![false; 80000];
-    pots.splice(40000..40000, pots_input);
+    for generation in 0..target {
+        if let Some((start, offset)) = seen.insert(tape.words.clone(), (generation, tape.offset)) {
+            let cycle = Cycle {
+                start,
+                period: generation - start,
+                shift: tape.offset - offset,
+            };
 
-    let mut last_sum = 0;
+            // current tape is the one from `start` moved by one period, so only the remainder is left to run
+            let remaining = target - cycle.start;
+            for _ in 0..(remaining % cycle.period) {
+                tape.step_into(rules, &mut next);
+                std::mem::swap(&mut tape, &mut next);
+            }
+            tape.offset += (remaining / cycle.period - 1) as i64 * cycle.shift;
 
-    let size = pots.len();
+            return (tape, Some(cycle));
+        }
 
-    let mut last_adds = Vec::with_capacity(100);
+        tape.step_into(rules, &mut next);
+        std::mem::swap(&mut tape, &mut next);
+    }
 
-    let mut stable_gen = 0;
+    (tape, None)
+}
 
-    for i in 0..10000 {
-        let p_clone = pots.clone();
-        for x in 2..(size - 2) {
-            let key = [
-                p_clone[x - 2],
-                p_clone[x - 1],
-                p_clone[x],
-                p_clone[x + 1],
-                p_clone[x + 2],
-            ];
-            pots[x] = rules[&key];
+/// Runs automaton for `generations` and draws every generation as a row, spanning all pots that ever had a plant
+fn space_time_diagram(mut tape: Tape, rules: &RuleTable, generations: u64) -> Bitmap {
+    let mut history = Vec::with_capacity(generations as usize + 1);
+    let mut next = Tape::default();
+    for _ in 0..generations {
+        tape.step_into(rules, &mut next);
+        history.push(std::mem::replace(&mut tape, next.clone()));
+    }
+    history.push(tape);
+
+    let lived = history.iter().filter(|tape| tape.len > 0);
+    let left = lived.clone().map(|tape| tape.offset).min().unwrap_or(0);
+    let right = lived.map(|tape| tape.offset + tape.len as i64).max().unwrap_or(0);
+
+    let mut diagram = Bitmap::new((right - left) as usize, history.len());
+    for (y, tape) in history.iter().enumerate() {
+        for i in (0..tape.len).filter(|i| tape.get(*i) == 1) {
+            diagram.set((tape.offset - left) as usize + i, y, true);
         }
-        let sum = pot_sum(&pots, 40000);
-        println!("{}", sum - last_sum);
-        last_adds.push(sum - last_sum);
-        if last_adds.len() == 101 {
-            let mut eq = true;
-            for win in last_adds.windows(2) {
-                if win[0] != win[1] {
-                    eq = false;
-                    break;
-                }
-            }
-            if eq {
-                stable_gen = i;
-                break;
-            }
-            last_adds.remove(0);
+    }
+    diagram
+}
+
+#[derive(Debug)]
+pub struct Options {
+    /// Wolfram code of radius 1 rule to run from a single plant instead of the puzzle input
+    rule: Option<u8>,
+    generations: u64,
+    render: bool,
+    export: Option<String>,
+    scale: usize,
+}
+
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        Ok(Self {
+            rule: args
+                .opt_value_from_str("--rule")
+                .context("Did not get valid --rule parameter value")?,
+            generations: args
+                .opt_value_from_str("--generations")
+                .context("Did not get valid --generations parameter value")?
+                .unwrap_or(20),
+            render: args.contains("--render"),
+            export: args
+                .opt_value_from_str("--export")
+                .context("Did not get valid --export parameter value")?,
+            scale: args
+                .opt_value_from_str("--scale")
+                .context("Did not get valid --scale parameter value")?
+                .unwrap_or(1),
+        })
+    }
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    match options.rule {
+        Some(code) => solve_wolfram(code, options.generations)?,
+        None => {
+            solve()?;
+            solve_extra()?;
+        }
+    }
+
+    if !options.render && options.export.is_none() {
+        return Ok(());
+    }
+
+    let (tape, rules) = match options.rule {
+        Some(code) => (Tape::new(&[true]), RuleTable::wolfram(code)?),
+        None => {
+            let (pots, rules) = parse_input();
+            (Tape::new(&pots), RuleTable::new(&rules)?)
+        }
+    };
+    let diagram = space_time_diagram(tape, &rules, options.generations);
+
+    if options.render {
+        let digits = options.generations.to_string().len();
+        for (generation, row) in diagram.render('#', '.').lines().enumerate() {
+            println!("{:>width$}: {}", generation, row, width = digits);
         }
-        last_sum = sum;
     }
 
-    let mut sum = pot_sum(&pots, 40000) as isize;
+    if let Some(path) = &options.export {
+        diagram.save_pbm(path, options.scale)?;
+        println!("Diagram of {} generations saved to {}", options.generations, path);
+    }
+
+    Ok(())
+}
+
+fn solve() -> Result<()> {
+    let (pots, rules) = parse_input();
+
+    let (tape, _) = extrapolate(Tape::new(&pots), &RuleTable::new(&rules)?, 20);
+
+    println!("Pot sum: {}", tape.pot_sum());
+
+    Ok(())
+}
+
+fn solve_extra() -> Result<()> {
+    let (pots, rules) = parse_input();
+
+    let (tape, cycle) = extrapolate(Tape::new(&pots), &RuleTable::new(&rules)?, 50_000_000_000);
+
+    if let Some(Cycle { start, period, shift }) = cycle {
+        println!(
+            "Pattern repeats from generation {} every {} generations, moving {} pots",
+            start, period, shift
+        );
+    }
+
+    println!("Pot sum: {}", tape.pot_sum());
+
+    Ok(())
+}
+
+fn solve_wolfram(code: u8, generations: u64) -> Result<()> {
+    let (tape, cycle) = extrapolate(Tape::new(&[true]), &RuleTable::wolfram(code)?, generations);
+
+    if let Some(Cycle { start, period, shift }) = cycle {
+        println!(
+            "Pattern repeats from generation {} every {} generations, moving {} pots",
+            start, period, shift
+        );
+    }
+
+    println!(
+        "Rule {} after {} generations: {} plants, pot sum {}",
+        code,
+        generations,
+        tape.plant_count(),
+        tape.pot_sum()
+    );
+
+    Ok(())
+}
+
+pub fn bench() -> Result<()> {
+    const GENERATIONS: u32 = 10_000;
+
+    let (pots, rules) = parse_input();
+    let table = RuleTable::new(&rules)?;
+
+    let unpacked_rules = rules
+        .iter()
+        .map(|(pattern, result)| {
+            let pattern = <[bool; 5]>::try_from(&pattern[..]).expect("Unpacked automaton only supports radius 2");
+            (pattern, *result)
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut tape = UnpackedTape {
+        pots: pots.clone(),
+        offset: 0,
+    };
+    for _ in 0..GENERATIONS {
+        tape = tape.step(&unpacked_rules);
+    }
+    let unpacked_time = start.elapsed();
+    let unpacked_sum = tape.pot_sum();
+
+    let start = Instant::now();
+    let mut tape = Tape::new(&pots);
+    let mut next = Tape::default();
+    for _ in 0..GENERATIONS {
+        tape.step_into(&table, &mut next);
+        std::mem::swap(&mut tape, &mut next);
+    }
+    let packed_time = start.elapsed();
+    let packed_sum = tape.pot_sum();
+
+    assert_eq!(unpacked_sum, packed_sum, "Automatons disagree on pot sum");
 
-    sum += (50_000_000_000 - stable_gen as isize - 1) * last_adds[0] as isize;
+    println!("{} generations, pot sum {}", GENERATIONS, packed_sum);
+    println!("Unpacked: {:03} seconds", unpacked_time.as_secs_f32());
+    println!("Packed:   {:03} seconds", packed_time.as_secs_f32());
 
-    println!("Pot sum: {}", sum);
+    Ok(())
 }
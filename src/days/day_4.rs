@@ -2,6 +2,11 @@ use nom::bytes::complete::tag;
 use nom::sequence::tuple;
 use nom::IResult;
 use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+
+use anyhow::{Context, Result};
+use pico_args::Arguments;
 
 #[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Copy, Clone)]
 struct Timestamp {
@@ -75,7 +80,44 @@ impl GuardAction {
 
 const INPUT: &str = include_str!("../inputs/day_4_input");
 
-pub fn solve() {
+#[derive(Debug)]
+pub struct Options {
+    report: bool,
+    csv: Option<String>,
+}
+
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        Ok(Self {
+            report: args.contains("--report"),
+            csv: args
+                .opt_value_from_str("--csv")
+                .context("Did not get valid --csv parameter value")?,
+        })
+    }
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    solve();
+    solve_extra();
+
+    if options.report || options.csv.is_some() {
+        let reports = guard_reports(&get_parsed_shifts());
+
+        if options.report {
+            print!("{}", report_text(&reports));
+        }
+
+        if let Some(path) = &options.csv {
+            fs::write(path, report_csv(&reports)).with_context(|| format!("Could not write {}", path))?;
+            println!("Report saved to {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+fn solve() {
     let shifts = get_parsed_shifts();
 
     let mut sleeping = HashMap::new();
@@ -121,7 +163,7 @@ pub fn solve() {
     println!("ID x minute = {}", sleepiest.0 * max.0 as u32);
 }
 
-pub fn solve_extra() {
+fn solve_extra() {
     let shifts = get_parsed_shifts();
 
     let mut shift_minutes = HashMap::new();
@@ -189,3 +231,122 @@ fn get_parsed_shifts() -> Vec<GuardShift> {
 
     shifts
 }
+
+/// Sleep habits of one guard over all of their shifts
+#[derive(Debug)]
+struct GuardReport {
+    id: u32,
+    shifts: u32,
+    asleep: u32,
+    /// how many shifts the guard slept through each minute of midnight hour
+    minutes: [u32; 60],
+}
+
+impl GuardReport {
+    /// Minute slept through most often, earliest one on tie, `None` if guard never slept
+    fn top_minute(&self) -> Option<(usize, u32)> {
+        self.minutes
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+    }
+}
+
+/// Collects report of every guard, sleepiest first
+fn guard_reports(shifts: &[GuardShift]) -> Vec<GuardReport> {
+    let mut reports = HashMap::new();
+
+    for shift in shifts {
+        let report = reports.entry(shift.id).or_insert(GuardReport {
+            id: shift.id,
+            shifts: 0,
+            asleep: 0,
+            minutes: [0; 60],
+        });
+        report.shifts += 1;
+        for (start, end) in &shift.asleep {
+            report.asleep += end.minute - start.minute;
+            for x in start.minute..end.minute {
+                report.minutes[x as usize] += 1;
+            }
+        }
+    }
+
+    let mut reports = reports.into_values().collect::<Vec<_>>();
+    reports.sort_by(|a, b| b.asleep.cmp(&a.asleep).then(a.id.cmp(&b.id)));
+    reports
+}
+
+/// Lists guards with a heat map of how often they sleep on each minute, shaded against the sleepiest minute of all guards
+fn report_text(reports: &[GuardReport]) -> String {
+    const SHADES: &[u8] = b" .:-=+*#%@";
+
+    let most = reports
+        .iter()
+        .flat_map(|report| report.minutes)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:>6} {:>6} {:>6} {:>9} |{:<60}|",
+        "guard", "shifts", "asleep", "top", "00        10        20        30        40        50"
+    )
+    .unwrap();
+    for report in reports {
+        let top = match report.top_minute() {
+            Some((minute, count)) => format!("{:02} x{}", minute, count),
+            None => "-".to_owned(),
+        };
+        // any sleep at all shows up as at least the lightest shade
+        let heat = report
+            .minutes
+            .iter()
+            .map(|count| {
+                let shade = (*count as usize * (SHADES.len() - 1)).div_ceil(most as usize);
+                char::from(SHADES[shade])
+            })
+            .collect::<String>();
+        writeln!(
+            out,
+            "{:>6} {:>6} {:>6} {:>9} |{}|",
+            format!("#{}", report.id),
+            report.shifts,
+            report.asleep,
+            top,
+            heat
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn report_csv(reports: &[GuardReport]) -> String {
+    let mut out = String::from("guard,shifts,asleep,top_minute,top_minute_count");
+    for minute in 0..60 {
+        write!(out, ",minute_{:02}", minute).unwrap();
+    }
+    out.push('\n');
+
+    for report in reports {
+        let (minute, count) = match report.top_minute() {
+            Some((minute, count)) => (minute.to_string(), count),
+            None => (String::new(), 0),
+        };
+        write!(
+            out,
+            "{},{},{},{},{}",
+            report.id, report.shifts, report.asleep, minute, count
+        )
+        .unwrap();
+        for count in &report.minutes {
+            write!(out, ",{}", count).unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -1,26 +1,172 @@
+use std::str::FromStr;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use pico_args::Arguments;
+
 const INPUT: &str = include_str!("../inputs/day_5_input");
 
-pub fn solve() {
-    let mut polymer = INPUT.to_owned();
-    println!("Final polymer length: {}", react_polymer(&mut polymer));
+/// Which units react with each other, every unit has at most one partner
+#[derive(Debug, Clone)]
+struct PairTable {
+    partners: [Option<u8>; 256],
+}
+
+impl PairTable {
+    fn case_pairs() -> Self {
+        let mut partners = [None; 256];
+        for lower in b'a'..=b'z' {
+            let upper = lower.to_ascii_uppercase();
+            partners[lower as usize] = Some(upper);
+            partners[upper as usize] = Some(lower);
+        }
+        Self { partners }
+    }
+
+    fn reacts(&self, first: u8, second: u8) -> bool {
+        self.partners[first as usize] == Some(second)
+    }
+
+    /// Unit standing for itself and its partner when removing units, case pairs go by lowercase letter
+    fn kind(&self, unit: u8) -> u8 {
+        self.partners[unit as usize].map_or(unit, |partner| partner.max(unit))
+    }
+}
+
+impl FromStr for PairTable {
+    type Err = anyhow::Error;
+
+    /// Parses comma separated pairs of units like `aA,bB`
+    fn from_str(s: &str) -> Result<Self> {
+        let mut partners = [None; 256];
+        for pair in s.split(',') {
+            let (first, second) = match pair.as_bytes() {
+                [first, second] => (*first, *second),
+                _ => anyhow::bail!("pair {:?} has to be exactly two units", pair),
+            };
+            for unit in [first, second] {
+                if let Some(partner) = partners[unit as usize] {
+                    anyhow::bail!(
+                        "unit '{}' already reacts with '{}'",
+                        char::from(unit),
+                        char::from(partner)
+                    );
+                }
+            }
+            partners[first as usize] = Some(second);
+            partners[second as usize] = Some(first);
+        }
+        Ok(Self { partners })
+    }
 }
 
-pub fn solve_extra() {
-    let min_polymer = (b'a'..b'z')
+#[derive(Debug)]
+pub struct Options {
+    pairs: PairTable,
+}
+
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        Ok(Self {
+            pairs: args
+                .opt_value_from_str("--pairs")
+                .context("Did not get valid --pairs parameter value")?
+                .unwrap_or_else(PairTable::case_pairs),
+        })
+    }
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    solve(options);
+    solve_extra(options);
+
+    Ok(())
+}
+
+fn solve(options: &Options) {
+    println!(
+        "Final polymer length: {}",
+        react(polymer().iter().copied(), &options.pairs).len()
+    );
+}
+
+fn solve_extra(options: &Options) {
+    match best_removal(polymer(), &options.pairs) {
+        Some((unit, length)) => println!("Final best polymer length without '{}': {}", char::from(unit), length),
+        None => println!("Polymer has no units to remove"),
+    }
+}
+
+fn polymer() -> &'static [u8] {
+    INPUT.trim().as_bytes()
+}
+
+/// Unit kind which leaves shortest polymer after removing it, with that length
+fn best_removal(polymer: &[u8], pairs: &PairTable) -> Option<(u8, usize)> {
+    let mut kinds = polymer.iter().map(|unit| pairs.kind(*unit)).collect::<Vec<_>>();
+    kinds.sort_unstable();
+    kinds.dedup();
+
+    // removing a unit never stops other reactions from happening, so already reacted polymer gives the same result
+    let reacted = react(polymer.iter().copied(), pairs);
+    kinds
+        .into_iter()
+        .map(|kind| {
+            let without = reacted.iter().copied().filter(|unit| pairs.kind(*unit) != kind);
+            (kind, react(without, pairs).len())
+        })
+        .min_by(|a, b| a.1.cmp(&b.1))
+}
+
+/// Reacts polymer in one pass, each unit either cancels the last surviving one or stays on the stack
+fn react(polymer: impl IntoIterator<Item = u8>, pairs: &PairTable) -> Vec<u8> {
+    let mut stack = Vec::new();
+    for unit in polymer {
+        match stack.last() {
+            Some(&last) if pairs.reacts(last, unit) => {
+                stack.pop();
+            }
+            _ => stack.push(unit),
+        }
+    }
+    stack
+}
+
+pub fn bench() -> Result<()> {
+    let start = Instant::now();
+    let mut rescanned = INPUT.trim().to_owned();
+    let rescan_length = react_polymer(&mut rescanned);
+    let rescan_best = (b'a'..=b'z')
         .map(|x| {
-            let mut polymer = remove_all(x, INPUT.to_owned());
-            let react = react_polymer(&mut polymer);
-            (x, react)
+            let mut polymer = remove_all(x, INPUT.trim().to_owned());
+            (x, react_polymer(&mut polymer))
         })
         .min_by(|a, b| a.1.cmp(&b.1))
         .unwrap();
+    let rescan_time = start.elapsed();
+
+    let start = Instant::now();
+    let pairs = PairTable::case_pairs();
+    let stack_length = react(polymer().iter().copied(), &pairs).len();
+    let stack_best = best_removal(polymer(), &pairs).context("Polymer has no units to remove")?;
+    let stack_time = start.elapsed();
+
+    assert_eq!(rescan_length, stack_length, "Reactions disagree on polymer length");
+    assert_eq!(rescan_best, stack_best, "Reactions disagree on best removal");
+
     println!(
-        "Final best polymer length without '{}': {}",
-        char::from(min_polymer.0),
-        min_polymer.1
+        "Polymer length {}, {} without '{}'",
+        stack_length,
+        stack_best.1,
+        char::from(stack_best.0)
     );
+    println!("Rescan: {:03} seconds", rescan_time.as_secs_f32());
+    println!("Stack:  {:03} seconds", stack_time.as_secs_f32());
+
+    Ok(())
 }
 
+/// Previous reaction which rescans polymer from the start after every reaction, kept to compare against in `bench`
 fn react_polymer(polymer: &mut String) -> usize {
     while let Some(m) = find_reaction(polymer) {
         polymer.replace_range(m..=(m + 1), "");
@@ -29,19 +175,9 @@ fn react_polymer(polymer: &mut String) -> usize {
 }
 
 fn find_reaction(i: &str) -> Option<usize> {
-    let len = i.len();
-    let bytes = i.as_bytes();
-    for x in 0..(len - 1) {
-        let chars = &bytes[x..=(x + 1)];
-        let first = chars[0];
-        let second = chars[1];
-        if first.to_ascii_uppercase() == second.to_ascii_uppercase() {
-            if first.is_ascii_uppercase() != second.is_ascii_uppercase() {
-                return Some(x);
-            }
-        }
-    }
-    None
+    i.as_bytes()
+        .windows(2)
+        .position(|pair| pair[0] != pair[1] && pair[0].eq_ignore_ascii_case(&pair[1]))
 }
 
 fn remove_all(ch: u8, i: String) -> String {
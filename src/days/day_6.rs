@@ -1,125 +1,435 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
 
+use anyhow::{Context, Result};
 use nom::IResult;
+use pico_args::Arguments;
+
+use crate::image::{distinct_colour, Colour, Pixmap};
 
 const INPUT: &str = include_str!("../inputs/day_6_input");
 
+/// Marks cell with more than one closest coordinate
+const TIED: usize = usize::MAX;
+
 #[derive(Debug)]
-struct Coords(u32, u32);
+struct Coords(i32, i32);
 
 impl Coords {
     fn parse(i: &str) -> IResult<&str, Self> {
         use nom::bytes::complete::tag;
-        use nom::character::complete::digit1;
-        use nom::combinator::map;
-        use nom::sequence::tuple;
-        let (i, state) = map(
-            tuple((digit1, tag(", "), digit1)),
-            |(x, _, y): (&str, &str, &str)| Self(x.parse().unwrap(), y.parse().unwrap()),
-        )(i)?;
+        use nom::character::complete::{char, digit1};
+        use nom::combinator::{map, opt, recognize};
+        use nom::sequence::{pair, tuple};
+        let num = || recognize(pair(opt(char('-')), digit1));
+        let (i, state) = map(tuple((num(), tag(", "), num())), |(x, _, y): (&str, &str, &str)| {
+            Self(x.parse().unwrap(), y.parse().unwrap())
+        })(i)?;
         Ok((i, state))
     }
 }
 
-pub fn solve() {
-    let coords = get_coords();
-    let (mut field, x_size, y_size) = get_field(&coords);
+#[derive(Debug, Copy, Clone)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+}
+
+impl Metric {
+    fn distance(self, (a_x, a_y): (i32, i32), (b_x, b_y): (i32, i32)) -> f64 {
+        let (d_x, d_y) = ((a_x - b_x).abs() as f64, (a_y - b_y).abs() as f64);
+        match self {
+            Metric::Manhattan => d_x + d_y,
+            Metric::Chebyshev => d_x.max(d_y),
+            Metric::Euclidean => d_x.hypot(d_y),
+        }
+    }
 
-    let index_to_coords = |i| (i % x_size, i / x_size);
+    /// Moves which change distance by exactly one, `None` if the metric can't be walked in whole steps
+    fn steps(self) -> Option<&'static [(i32, i32)]> {
+        match self {
+            Metric::Manhattan => Some(&[(-1, 0), (1, 0), (0, -1), (0, 1)]),
+            Metric::Chebyshev => Some(&[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)]),
+            Metric::Euclidean => None,
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            "euclidean" => Ok(Metric::Euclidean),
+            _ => anyhow::bail!("unknown metric {}, expected manhattan, chebyshev or euclidean", s),
+        }
+    }
+}
+
+/// Smallest rectangle containing all coordinates
+#[derive(Debug)]
+struct Bounds {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+impl Bounds {
+    fn new(coords: &[Coords]) -> Self {
+        Self {
+            min_x: coords.iter().map(|a| a.0).min().unwrap(),
+            min_y: coords.iter().map(|a| a.1).min().unwrap(),
+            max_x: coords.iter().map(|a| a.0).max().unwrap(),
+            max_y: coords.iter().map(|a| a.1).max().unwrap(),
+        }
+    }
+
+    fn width(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
 
-    for (i, elem) in field.iter_mut().enumerate() {
-        let (x, y) = index_to_coords(i);
-        let mut distances = coords
+    fn index(&self, x: i32, y: i32) -> usize {
+        (x - self.min_x) as usize + (y - self.min_y) as usize * self.width()
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        (self.min_x..=self.max_x).contains(&x) && (self.min_y..=self.max_y).contains(&y)
+    }
+
+    fn on_border(&self, x: i32, y: i32) -> bool {
+        x == self.min_x || x == self.max_x || y == self.min_y || y == self.max_y
+    }
+
+    fn grow(&self, margin: i32) -> Self {
+        Self {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+
+    fn extend(&mut self, x: i32, y: i32) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (self.min_y..=self.max_y).flat_map(move |y| (self.min_x..=self.max_x).map(move |x| (x, y)))
+    }
+}
+
+/// Finds closest coordinate of every cell in bounds
+fn closest_owners(coords: &[Coords], bounds: &Bounds, metric: Metric) -> Vec<usize> {
+    match metric.steps() {
+        Some(steps) => grow_owners(coords, bounds, steps),
+        None => bounds.cells().map(|cell| closest_owner(coords, cell, metric)).collect(),
+    }
+}
+
+/// Finds closest coordinate of a single cell by measuring distance to all of them
+fn closest_owner(coords: &[Coords], cell: (i32, i32), metric: Metric) -> usize {
+    let mut owner = TIED;
+    let mut min = f64::INFINITY;
+    for (index, a) in coords.iter().enumerate() {
+        let distance = metric.distance((a.0, a.1), cell);
+        if distance < min {
+            min = distance;
+            owner = index;
+        } else if distance == min {
+            owner = TIED;
+        }
+    }
+    owner
+}
+
+/// Finds coordinates whose regions keep going forever
+fn infinite_owners(coords: &[Coords], metric: Metric) -> HashSet<usize> {
+    match metric {
+        // stepping out of bounding box adds one to distance from every coordinate, so border cells keep their owner
+        // all the way out and every cell outside can step back to the border without changing owner
+        Metric::Manhattan => {
+            let bounds = Bounds::new(coords);
+            bounds
+                .cells()
+                .filter(|(x, y)| bounds.on_border(*x, *y))
+                .map(|cell| closest_owner(coords, cell, metric))
+                .collect()
+        }
+        // same as Manhattan in coordinates turned by 45 degrees, where only every other cell exists, so the border
+        // is two cells thick
+        Metric::Chebyshev => {
+            let (u_min, u_max) = (
+                coords.iter().map(|a| a.0 + a.1).min().unwrap(),
+                coords.iter().map(|a| a.0 + a.1).max().unwrap(),
+            );
+            let (v_min, v_max) = (
+                coords.iter().map(|a| a.0 - a.1).min().unwrap(),
+                coords.iter().map(|a| a.0 - a.1).max().unwrap(),
+            );
+            ((u_min - 1)..=(u_max + 1))
+                .flat_map(|u| ((v_min - 1)..=(v_max + 1)).map(move |v| (u, v)))
+                .filter(|(u, v)| (u - v) % 2 == 0)
+                .filter(|(u, v)| *u <= u_min || *u >= u_max || *v <= v_min || *v >= v_max)
+                .map(|(u, v)| closest_owner(coords, ((u + v) / 2, (u - v) / 2), metric))
+                .collect()
+        }
+        // only coordinates on the edge of convex hull have regions without an end
+        Metric::Euclidean => (0..coords.len()).filter(|index| on_hull(coords, *index)).collect(),
+    }
+}
+
+/// Checks if some line through the coordinate and another one has all coordinates on one side
+fn on_hull(coords: &[Coords], index: usize) -> bool {
+    let Coords(a_x, a_y) = coords[index];
+    let mut others = coords.iter().filter(|b| (b.0, b.1) != (a_x, a_y)).peekable();
+    if others.peek().is_none() {
+        return true;
+    }
+
+    others.any(|Coords(b_x, b_y)| {
+        let sides = coords
             .iter()
-            .enumerate()
-            .map(|(index, a)| {
-                (
-                    index,
-                    manhattan((a.0 as i32, a.1 as i32), (x as i32, y as i32)),
-                )
+            .map(|Coords(c_x, c_y)| {
+                ((b_x - a_x) as i64 * (c_y - a_y) as i64 - (b_y - a_y) as i64 * (c_x - a_x) as i64).signum()
             })
             .collect::<Vec<_>>();
+        sides.iter().all(|side| *side >= 0) || sides.iter().all(|side| *side <= 0)
+    })
+}
 
-        distances.sort_by(|a, b| a.1.cmp(&b.1));
+/// Size of the biggest region which doesn't keep going forever
+fn biggest_region(coords: &[Coords], metric: Metric) -> Option<usize> {
+    let infinite = infinite_owners(coords, metric);
 
-        if distances[0].1 != distances[1].1 {
-            *elem = distances[0].0
+    // finite regions can reach past bounding box, so grow it until none of them touches the border
+    let mut margin = 0;
+    let owners = loop {
+        let bounds = Bounds::new(coords).grow(margin);
+        let owners = closest_owners(coords, &bounds, metric);
+        let cut = bounds
+            .cells()
+            .filter(|(x, y)| bounds.on_border(*x, *y))
+            .map(|(x, y)| owners[bounds.index(x, y)])
+            .any(|owner| owner != TIED && !infinite.contains(&owner));
+        if !cut {
+            break owners;
         }
-    }
+        margin = margin * 2 + 1;
+    };
 
     let mut stats = HashMap::new();
 
-    for elem in &field {
-        *stats.entry(*elem).or_insert(0) += 1;
+    for owner in owners
+        .iter()
+        .filter(|owner| **owner != TIED && !infinite.contains(owner))
+    {
+        *stats.entry(*owner).or_insert(0) += 1;
     }
 
-    stats.remove(&usize::MAX);
+    stats.values().max().copied()
+}
+
+/// Grows all regions at once, one step of distance at a time
+fn grow_owners(coords: &[Coords], bounds: &Bounds, steps: &[(i32, i32)]) -> Vec<usize> {
+    let mut owners = vec![TIED; bounds.width() * bounds.height()];
+    let mut distances = vec![u32::MAX; owners.len()];
+    let mut frontier = VecDeque::new();
 
-    // now remove all entries which touch the borders
-    for x in 0..x_size {
-        stats.remove(&field[x]);
+    for (owner, &Coords(x, y)) in coords.iter().enumerate() {
+        let index = bounds.index(x, y);
+        // coordinates on the same spot tie
+        owners[index] = if distances[index] == 0 { TIED } else { owner };
+        distances[index] = 0;
+        frontier.push_back((x, y));
     }
-    for x in ((y_size - 1) * x_size)..((y_size - 1) * x_size + x_size) {
-        stats.remove(&field[x]);
+
+    while let Some((x, y)) = frontier.pop_front() {
+        let index = bounds.index(x, y);
+        let (owner, distance) = (owners[index], distances[index]);
+
+        for (n_x, n_y) in steps.iter().map(|(d_x, d_y)| (x + d_x, y + d_y)) {
+            if !bounds.contains(n_x, n_y) {
+                continue;
+            }
+
+            // every cell is reached first from all of its closest coordinates, so any disagreement means a tie
+            let n_index = bounds.index(n_x, n_y);
+            if distances[n_index] == u32::MAX {
+                distances[n_index] = distance + 1;
+                owners[n_index] = owner;
+                frontier.push_back((n_x, n_y));
+            } else if distances[n_index] == distance + 1 && owners[n_index] != owner {
+                owners[n_index] = TIED;
+            }
+        }
     }
-    for y in 0..y_size {
-        let base = y * x_size;
-        stats.remove(&field[base]);
-        stats.remove(&field[base + x_size - 1]);
+
+    owners
+}
+
+#[derive(Debug)]
+pub struct Options {
+    metric: Metric,
+    /// total distance to all coordinates, which cells in safe region stay under
+    threshold: f64,
+    export: Option<String>,
+    scale: usize,
+}
+
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        Ok(Self {
+            metric: args
+                .opt_value_from_str("--metric")
+                .context("Did not get valid --metric parameter value")?
+                .unwrap_or(Metric::Manhattan),
+            threshold: args
+                .opt_value_from_str("--threshold")
+                .context("Did not get valid --threshold parameter value")?
+                .unwrap_or(10_000.0),
+            export: args
+                .opt_value_from_str("--export")
+                .context("Did not get valid --export parameter value")?,
+            scale: args
+                .opt_value_from_str("--scale")
+                .context("Did not get valid --scale parameter value")?
+                .unwrap_or(2),
+        })
     }
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    solve(options);
+    solve_extra(options);
 
-    let mut sorted_stats = stats.iter().collect::<Vec<_>>();
-    sorted_stats.sort_by(|a, b| b.1.cmp(&a.1));
+    if let Some(path) = &options.export {
+        draw_regions(options).save_ppm(path, options.scale)?;
+        println!("Regions saved to {}", path);
+    }
 
-    println!("Biggest region size is {}", sorted_stats[0].1);
+    Ok(())
 }
 
-pub fn solve_extra() {
+fn solve(options: &Options) {
     let coords = get_coords();
-    let (mut field, x_size, _y_size) = get_field(&coords);
 
-    let index_to_coords = |i| (i % x_size, i / x_size);
-
-    for (i, elem) in field.iter_mut().enumerate() {
-        let (x, y) = index_to_coords(i);
-        let dist = coords
-            .iter()
-            .map(|a| manhattan((a.0 as i32, a.1 as i32), (x as i32, y as i32)))
-            .sum::<i32>() as usize;
-        *elem = dist;
+    match biggest_region(&coords, options.metric) {
+        Some(size) => println!("Biggest region size is {}", size),
+        None => println!("All regions are infinite"),
     }
-    let reg_size = field.iter().filter(|a| **a < 10_000).count();
+}
 
-    println!("Closest region size is {}", reg_size);
+/// Cells with total distance to all coordinates under threshold
+fn safe_cells(coords: &[Coords], metric: Metric, threshold: f64) -> impl Iterator<Item = (i32, i32)> + '_ {
+    // every step away from the bounding box adds at least one to distance from every coordinate
+    let margin = (threshold / coords.len() as f64).ceil().max(0.0) as i32;
+    let Bounds {
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+    } = Bounds::new(coords).grow(margin);
+
+    let cells = (min_y..=max_y).flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)));
+    cells.filter(move |cell| {
+        let dist = coords.iter().map(|a| metric.distance((a.0, a.1), *cell)).sum::<f64>();
+        dist < threshold
+    })
 }
 
-fn get_coords() -> Vec<Coords> {
-    INPUT
-        .lines()
-        .map(|i| Coords::parse(i).unwrap().1)
-        .collect::<Vec<_>>()
+fn solve_extra(options: &Options) {
+    let coords = get_coords();
+
+    let reg_size = safe_cells(&coords, options.metric, options.threshold).count();
+
+    println!("Closest region size is {}", reg_size);
 }
 
-fn get_field(coords: &[Coords]) -> (Vec<usize>, usize, usize) {
-    let (mut max_x, mut max_y) = (0, 0);
+/// Draws closest coordinate of every cell, safe region is lightened and coordinates are black
+fn draw_regions(options: &Options) -> Pixmap {
+    const TIED_COLOUR: Colour = [128, 128, 128];
+    const CENTRE_COLOUR: Colour = [0, 0, 0];
 
-    for center in coords {
-        if center.0 > max_x {
-            max_x = center.0;
-        }
+    let coords = get_coords();
+    let safe = safe_cells(&coords, options.metric, options.threshold).collect::<HashSet<_>>();
 
-        if center.1 > max_y {
-            max_y = center.1;
+    // safe region may spill out of bounding box
+    let mut bounds = Bounds::new(&coords);
+    for (x, y) in &safe {
+        bounds.extend(*x, *y);
+    }
+    let owners = closest_owners(&coords, &bounds, options.metric);
+
+    let mut pixmap = Pixmap::new(bounds.width(), bounds.height(), TIED_COLOUR);
+    for (x, y) in bounds.cells() {
+        let mut colour = match owners[bounds.index(x, y)] {
+            TIED => TIED_COLOUR,
+            owner => distinct_colour(owner),
+        };
+        if safe.contains(&(x, y)) {
+            colour = colour.map(|channel| channel / 2 + 128);
         }
+        pixmap.set((x - bounds.min_x) as usize, (y - bounds.min_y) as usize, colour);
+    }
+    for Coords(x, y) in &coords {
+        pixmap.set((x - bounds.min_x) as usize, (y - bounds.min_y) as usize, CENTRE_COLOUR);
     }
-    let x_size = (max_x + 1) as usize;
-    let y_size = (max_y + 1) as usize;
 
-    let field = vec![usize::MAX; x_size * y_size];
+    pixmap
+}
 
-    (field, x_size, y_size)
+fn get_coords() -> Vec<Coords> {
+    INPUT.lines().map(|i| Coords::parse(i).unwrap().1).collect::<Vec<_>>()
 }
 
-fn manhattan((a_x, a_y): (i32, i32), (b_x, b_y): (i32, i32)) -> i32 {
-    (a_x - b_x).abs() + (a_y - b_y).abs()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Example from the puzzle
+    fn example() -> Vec<Coords> {
+        vec![
+            Coords(1, 1),
+            Coords(1, 6),
+            Coords(8, 3),
+            Coords(3, 4),
+            Coords(5, 5),
+            Coords(8, 9),
+        ]
+    }
+
+    /// Last coordinate sits inside the triangle, just above its bottom edge, so its region pokes out below the
+    /// bounding box
+    fn triangle() -> Vec<Coords> {
+        vec![Coords(0, 0), Coords(20, 0), Coords(10, 20), Coords(10, 1)]
+    }
+
+    #[test]
+    fn manhattan_regions() {
+        assert_eq!(biggest_region(&example(), Metric::Manhattan), Some(17));
+        assert_eq!(biggest_region(&triangle(), Metric::Manhattan), None);
+    }
+
+    #[test]
+    fn chebyshev_regions() {
+        assert_eq!(biggest_region(&example(), Metric::Chebyshev), Some(10));
+        assert_eq!(biggest_region(&triangle(), Metric::Chebyshev), Some(181));
+    }
+
+    #[test]
+    fn euclidean_regions() {
+        assert_eq!(biggest_region(&example(), Metric::Euclidean), Some(16));
+        assert_eq!(biggest_region(&triangle(), Metric::Euclidean), Some(360));
+    }
 }
@@ -1,189 +1,542 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::Write;
+use std::fs;
 
+use anyhow::{Context, Result};
 use nom::IResult;
+use pico_args::Arguments;
 
 const INPUT: &str = include_str!("../inputs/day_7_input");
 
 #[derive(Debug)]
 struct Step {
-    name: char,
-    allows: char,
+    name: String,
+    allows: String,
 }
 
 impl Step {
     fn parse(i: &str) -> IResult<&str, Self> {
-        use nom::bytes::complete::tag;
-        use nom::character::complete::anychar;
+        use nom::bytes::complete::{tag, take_till1};
         use nom::combinator::map;
         use nom::sequence::tuple;
+        let name = || take_till1(|c: char| c.is_whitespace());
         let (i, step) = map(
             tuple((
                 tag("Step "),
-                anychar,
+                name(),
                 tag(" must be finished before step "),
-                anychar,
+                name(),
                 tag(" can begin."),
             )),
-            |(_, name, _, allows, _): (&str, char, &str, char, &str)| Self { name, allows },
+            |(_, name, _, allows, _): (&str, &str, &str, &str, &str)| Self {
+                name: name.to_owned(),
+                allows: allows.to_owned(),
+            },
         )(i)?;
 
         Ok((i, step))
     }
 }
 
+/// Step dependencies, steps are numbered in order of their names
 #[derive(Debug)]
-struct Node {
-    name: char,
-    used: bool,
-    requisites: HashMap<char, bool>,
+struct Graph {
+    names: Vec<String>,
+    /// steps which can't begin before the indexed one is finished
+    allows: Vec<Vec<usize>>,
+    /// number of steps the indexed one waits for
+    in_degree: Vec<usize>,
+}
+
+impl Graph {
+    fn new(steps: &[Step]) -> Self {
+        let mut names = steps
+            .iter()
+            .flat_map(|step| vec![step.name.clone(), step.allows.clone()])
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+
+        let index = |name| names.binary_search(name).unwrap();
+
+        let mut allows = vec![Vec::new(); names.len()];
+        let mut in_degree = vec![0; names.len()];
+        for step in steps {
+            allows[index(&step.name)].push(index(&step.allows));
+            in_degree[index(&step.allows)] += 1;
+        }
+
+        Self {
+            names,
+            allows,
+            in_degree,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Steps with nothing to wait for, lowest name on top
+    fn ready_steps(&self) -> BinaryHeap<Reverse<usize>> {
+        (0..self.len())
+            .filter(|step| self.in_degree[*step] == 0)
+            .map(Reverse)
+            .collect()
+    }
+
+    /// Marks step as finished, pushing steps which are no longer waiting into `ready`
+    fn finish(&self, step: usize, in_degree: &mut [usize], ready: &mut BinaryHeap<Reverse<usize>>) {
+        for next in &self.allows[step] {
+            in_degree[*next] -= 1;
+            if in_degree[*next] == 0 {
+                ready.push(Reverse(*next));
+            }
+        }
+    }
+
+    /// Order in which steps get done, taking alphabetically first of all available steps
+    fn topological_order(&self) -> Result<Vec<usize>> {
+        let mut in_degree = self.in_degree.clone();
+        let mut ready = self.ready_steps();
+        let mut order = Vec::with_capacity(self.len());
+
+        while let Some(Reverse(step)) = ready.pop() {
+            order.push(step);
+            self.finish(step, &mut in_degree, &mut ready);
+        }
+
+        if order.len() != self.len() {
+            return Err(self.cycle_error(&in_degree));
+        }
+
+        Ok(order)
+    }
+
+    /// Longest chain of steps by duration, which no number of workers can finish faster, returned with its length
+    fn critical_path(&self, durations: &Durations) -> Result<(Vec<usize>, u64)> {
+        let mut earliest_start = vec![0; self.len()];
+        let mut finish = vec![0; self.len()];
+        // step whose finish held up the indexed one the longest
+        let mut waits_on = vec![None; self.len()];
+
+        for step in self.topological_order()? {
+            finish[step] = earliest_start[step] + durations.of(&self.names[step]);
+            for next in &self.allows[step] {
+                if finish[step] > earliest_start[*next] || waits_on[*next].is_none() {
+                    earliest_start[*next] = finish[step];
+                    waits_on[*next] = Some(step);
+                }
+            }
+        }
+
+        let last = match (0..self.len()).max_by_key(|step| (finish[*step], Reverse(*step))) {
+            Some(last) => last,
+            None => return Ok((Vec::new(), 0)),
+        };
+
+        let mut path = vec![last];
+        while let Some(step) = waits_on[*path.last().unwrap()] {
+            path.push(step);
+        }
+        path.reverse();
+
+        Ok((path, finish[last]))
+    }
+
+    /// Describes graph in DOT format, labeling steps with their position in `order` and work done on them
+    fn dot(&self, order: Option<&[usize]>, schedule: Option<&Schedule>) -> String {
+        let mut out = String::from("digraph steps {\n    rankdir=LR;\n");
+
+        for step in 0..self.len() {
+            let mut label = self.names[step].clone();
+            if let Some(position) = order.and_then(|order| order.iter().position(|a| *a == step)) {
+                write!(label, "\n#{}", position + 1).unwrap();
+            }
+            if let Some(task) = schedule.and_then(|schedule| schedule.tasks.iter().find(|task| task.step == step)) {
+                write!(label, "\nworker {}, {}-{}", task.worker + 1, task.start, task.end).unwrap();
+            }
+            writeln!(out, "    {} [label={}];", quote(&self.names[step]), quote(&label)).unwrap();
+        }
+
+        for (step, allows) in self.allows.iter().enumerate() {
+            for next in allows {
+                writeln!(
+                    out,
+                    "    {} -> {};",
+                    quote(&self.names[step]),
+                    quote(&self.names[*next])
+                )
+                .unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Joins step names, single letter names are written without separator
+    fn sequence(&self, steps: &[usize]) -> String {
+        let separator = if self.names.iter().all(|name| name.chars().count() == 1) {
+            ""
+        } else {
+            ", "
+        };
+        steps
+            .iter()
+            .map(|step| self.names[*step].as_str())
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    fn cycle_error(&self, in_degree: &[usize]) -> anyhow::Error {
+        let stuck = (0..self.len()).filter(|step| in_degree[*step] > 0).collect::<Vec<_>>();
+        anyhow::anyhow!(
+            "Steps \"{}\" can never begin, they are in or depend on a cycle",
+            self.sequence(&stuck)
+        )
+    }
 }
 
 #[derive(Debug)]
-struct Worker {
-    current_work: Option<char>,
-    time_remaining: usize,
+struct Durations {
+    base: u64,
+    /// durations of particular steps, replacing the computed ones
+    steps: HashMap<String, u64>,
 }
 
-impl Worker {
-    pub fn new() -> Self {
-        Worker {
-            current_work: None,
-            time_remaining: 0,
+impl Durations {
+    /// Single letter steps take one more second for every letter of the alphabet, other steps take `base`
+    fn of(&self, name: &str) -> u64 {
+        if let Some(duration) = self.steps.get(name) {
+            return *duration;
+        }
+
+        match name.as_bytes() {
+            [letter @ b'A'..=b'Z'] => self.base + (letter - b'A') as u64 + 1,
+            _ => self.base,
         }
     }
 }
 
-pub fn solve() {
-    let steps = INPUT
-        .lines()
-        .map(|i| Step::parse(i).unwrap().1)
-        .collect::<Vec<_>>();
+#[derive(Debug)]
+struct Task {
+    step: usize,
+    worker: usize,
+    start: u64,
+    end: u64,
+}
 
-    let mut nodes = generate_nodes(&steps);
+#[derive(Debug)]
+struct Schedule {
+    /// steps in order of finishing
+    sequence: Vec<usize>,
+    /// work done by workers, in order of starting
+    tasks: Vec<Task>,
+    total_time: u64,
+}
 
-    let mut sequence = String::with_capacity(nodes.len());
+impl Schedule {
+    /// Draws one row per task, marking steps on `critical` path with `*`
+    fn gantt(&self, graph: &Graph, critical: &[usize]) -> String {
+        const WIDTH: u64 = 80;
+
+        let name_width = self
+            .tasks
+            .iter()
+            .map(|task| graph.names[task.step].chars().count())
+            .max()
+            .unwrap_or(0);
+        let total_time = self.total_time.max(1);
+        let column = |time: u64| (time * WIDTH / total_time) as usize;
+
+        let mut out = String::new();
+        for task in &self.tasks {
+            // every task gets at least one column, even when scaled down
+            let start = column(task.start).min(WIDTH as usize - 1);
+            let end = column(task.end).max(start + 1);
+            writeln!(
+                out,
+                "{}{:<name_width$} w{:<2} |{:start$}{:#<len$}{:rest$}| {:>5} - {:<5}",
+                if critical.contains(&task.step) { '*' } else { ' ' },
+                graph.names[task.step],
+                task.worker + 1,
+                "",
+                "",
+                "",
+                task.start,
+                task.end,
+                name_width = name_width,
+                start = start,
+                len = end - start,
+                rest = WIDTH as usize - end,
+            )
+            .unwrap();
+        }
+        out
+    }
 
-    while let Some(n) = find_next(&nodes) {
-        let next = n.name;
-        apply_req(&mut nodes, next);
-        sequence.push(next);
+    fn csv(&self, graph: &Graph, critical: &[usize]) -> String {
+        let mut out = String::from("step,worker,start,end,critical\n");
+        for task in &self.tasks {
+            let name = &graph.names[task.step];
+            let name = if name.contains([',', '"']) {
+                format!("\"{}\"", name.replace('"', "\"\""))
+            } else {
+                name.clone()
+            };
+            writeln!(
+                out,
+                "{},{},{},{},{}",
+                name,
+                task.worker + 1,
+                task.start,
+                task.end,
+                critical.contains(&task.step)
+            )
+            .unwrap();
+        }
+        out
     }
 
-    println!("Graph sequence is \"{}\"", sequence);
+    fn json(&self, graph: &Graph, critical: &[usize], critical_time: u64) -> String {
+        let tasks = self
+            .tasks
+            .iter()
+            .map(|task| {
+                format!(
+                    "    {{\"step\": {}, \"worker\": {}, \"start\": {}, \"end\": {}, \"critical\": {}}}",
+                    quote(&graph.names[task.step]),
+                    task.worker + 1,
+                    task.start,
+                    task.end,
+                    critical.contains(&task.step)
+                )
+            })
+            .collect::<Vec<_>>();
+        let critical = critical
+            .iter()
+            .map(|step| quote(&graph.names[*step]))
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\n  \"total_time\": {},\n  \"critical_time\": {},\n  \"critical_path\": [{}],\n  \"tasks\": [\n{}\n  ]\n}}\n",
+            self.total_time,
+            critical_time,
+            critical.join(", "),
+            tasks.join(",\n")
+        )
+    }
 }
 
-pub fn solve_extra() {
-    let steps = INPUT
-        .lines()
-        .map(|i| Step::parse(i).unwrap().1)
-        .collect::<Vec<_>>();
-
-    let mut nodes = generate_nodes(&steps);
+/// Wraps string in quotes, escaping it for JSON and DOT
+fn quote(i: &str) -> String {
+    let mut out = String::with_capacity(i.len() + 2);
+    out.push('"');
+    for c in i.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
 
-    let mut sequence = String::with_capacity(nodes.len());
+/// Runs the steps on `workers` workers, jumping from one finished step to the next
+fn simulate(graph: &Graph, workers: usize, durations: &Durations) -> Result<Schedule> {
+    let mut in_degree = graph.in_degree.clone();
+    let mut ready = graph.ready_steps();
 
-    const WORKERS: usize = 5;
+    // lowest numbered idle worker gets work first
+    let mut idle = (0..workers).map(Reverse).collect::<BinaryHeap<_>>();
+    // `(end, worker, step)` of steps in progress, earliest end on top
+    let mut running = BinaryHeap::new();
 
-    let mut workers = Vec::with_capacity(WORKERS);
-    workers.resize_with(WORKERS, || Worker::new());
+    let mut sequence = Vec::with_capacity(graph.len());
+    let mut tasks = Vec::with_capacity(graph.len());
     let mut time = 0;
 
     loop {
-        for worker in &mut workers {
-            if let Some(work) = worker.current_work {
-                if worker.time_remaining > 1 {
-                    // apply time step
-                    worker.time_remaining -= 1;
-                } else {
-                    // work done
-                    *worker = Worker::new();
-                    apply_req(&mut nodes, work);
-                    sequence.push(work);
-                }
-            }
+        while !idle.is_empty() && !ready.is_empty() {
+            let Reverse(worker) = idle.pop().unwrap();
+            let Reverse(step) = ready.pop().unwrap();
+            let end = time + durations.of(&graph.names[step]);
+            running.push(Reverse((end, worker, step)));
+            tasks.push(Task {
+                step,
+                worker,
+                start: time,
+                end,
+            });
         }
-        // now try assign some work to every free worker
-        for worker in workers.iter_mut().filter(|w| w.current_work.is_none()) {
-            if let Some(work) = find_next_mut(&mut nodes) {
-                work.used = true;
-                worker.current_work = Some(work.name);
-                worker.time_remaining = char_to_time(work.name);
+
+        time = match running.peek() {
+            Some(Reverse((end, _, _))) => *end,
+            None => break,
+        };
+
+        // finish everything ending now before handing out new work
+        while let Some(Reverse((end, worker, step))) = running.peek().copied() {
+            if end != time {
+                break;
             }
+            running.pop();
+            idle.push(Reverse(worker));
+            graph.finish(step, &mut in_degree, &mut ready);
+            sequence.push(step);
         }
-        if sequence.len() == nodes.len() {
-            break;
-        }
-        time += 1;
     }
 
-    //dbg!(&workers);
+    if sequence.len() != graph.len() {
+        return Err(graph.cycle_error(&in_degree));
+    }
+
+    Ok(Schedule {
+        sequence,
+        tasks,
+        total_time: time,
+    })
+}
+
+fn get_graph() -> Graph {
+    let steps = INPUT.lines().map(|i| Step::parse(i).unwrap().1).collect::<Vec<_>>();
 
-    println!("Parallel graph sequence is \"{}\"", sequence);
-    println!("Parallel graph took {} seconds", time);
+    Graph::new(&steps)
 }
 
-// this will horribly break on everything else than 'A'..='Z'
-fn char_to_time(c: char) -> usize {
-    let mut buf = [0; 4];
-    c.encode_utf8(&mut buf);
-    (buf[0] - b'A') as usize + 61
+#[derive(Debug)]
+pub struct Options {
+    workers: usize,
+    durations: Durations,
+    gantt: bool,
+    dot: Option<String>,
+    /// label DOT nodes with their place in the sequence
+    dot_order: bool,
+    /// label DOT nodes with worker assignment
+    dot_workers: bool,
+    csv: Option<String>,
+    json: Option<String>,
 }
 
-fn find_next(nodes: &[Node]) -> Option<&Node> {
-    let mut possible_nodes = nodes
-        .iter()
-        .filter(|n| n.requisites.iter().all(|(_, a)| *a) && !n.used)
-        .collect::<Vec<_>>();
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        let workers = args
+            .opt_value_from_str("--workers")
+            .context("Did not get valid --workers parameter value")?
+            .unwrap_or(5);
+        if workers == 0 {
+            anyhow::bail!("--workers has to be at least 1");
+        }
 
-    if possible_nodes.len() == 0 {
-        return None;
+        Ok(Self {
+            workers,
+            durations: Durations {
+                base: args
+                    .opt_value_from_str("--base-duration")
+                    .context("Did not get valid --base-duration parameter value")?
+                    .unwrap_or(60),
+                steps: args
+                    .values_from_fn("--duration", parse_duration)
+                    .context("Did not get valid --duration parameter value, expected `step=seconds`")?
+                    .into_iter()
+                    .collect(),
+            },
+            gantt: args.contains("--gantt"),
+            dot: args
+                .opt_value_from_str("--dot")
+                .context("Did not get valid --dot parameter value")?,
+            dot_order: args.contains("--dot-order"),
+            dot_workers: args.contains("--dot-workers"),
+            csv: args
+                .opt_value_from_str("--csv")
+                .context("Did not get valid --csv parameter value")?,
+            json: args
+                .opt_value_from_str("--json")
+                .context("Did not get valid --json parameter value")?,
+        })
     }
+}
 
-    possible_nodes.sort_by(|a, b| a.name.cmp(&b.name));
-
-    Some(possible_nodes[0])
+fn parse_duration(i: &str) -> Result<(String, u64)> {
+    let (name, seconds) = i.split_once('=').context("missing `=`")?;
+    Ok((name.to_owned(), seconds.parse()?))
 }
 
-fn find_next_mut(nodes: &mut [Node]) -> Option<&mut Node> {
-    let mut possible_nodes = nodes
-        .iter_mut()
-        .filter(|n| n.requisites.iter().all(|(_, a)| *a) && !n.used)
-        .collect::<Vec<_>>();
+pub fn run(options: &Options) -> Result<()> {
+    solve()?;
+    solve_extra(options)?;
 
-    if possible_nodes.len() == 0 {
-        return None;
+    if let Some(path) = &options.dot {
+        export_dot(path, options)?;
     }
 
-    possible_nodes.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(())
+}
 
-    possible_nodes.pop()
+fn export_dot(path: &str, options: &Options) -> Result<()> {
+    let graph = get_graph();
+
+    let order = if options.dot_order {
+        Some(graph.topological_order()?)
+    } else {
+        None
+    };
+    let schedule = if options.dot_workers {
+        Some(simulate(&graph, options.workers, &options.durations)?)
+    } else {
+        None
+    };
+
+    fs::write(path, graph.dot(order.as_deref(), schedule.as_ref()))
+        .with_context(|| format!("Could not write {}", path))?;
+    println!("Step graph saved to {}", path);
+
+    Ok(())
 }
 
-fn apply_req(nodes: &mut [Node], finished: char) {
-    for node in nodes {
-        if node.name == finished {
-            node.used = true;
-        }
-        if let Some(val) = node.requisites.get_mut(&finished) {
-            *val = true;
-        }
-    }
+fn solve() -> Result<()> {
+    let graph = get_graph();
+
+    let order = graph.topological_order()?;
+    println!("Graph sequence is \"{}\"", graph.sequence(&order));
+
+    Ok(())
 }
 
-fn generate_nodes(steps: &[Step]) -> Vec<Node> {
-    let mut requisites = HashMap::new();
-    for step in steps {
-        let Step { name, allows } = step;
-        requisites.entry(*allows).or_insert(Vec::new()).push(*name);
-        requisites.entry(*name).or_insert(Vec::new());
+fn solve_extra(options: &Options) -> Result<()> {
+    let graph = get_graph();
+
+    let schedule = simulate(&graph, options.workers, &options.durations)?;
+
+    println!("Parallel graph sequence is \"{}\"", graph.sequence(&schedule.sequence));
+    println!("Parallel graph took {} seconds", schedule.total_time);
+
+    let (critical, critical_time) = graph.critical_path(&options.durations)?;
+    println!(
+        "Critical path \"{}\" takes {} seconds",
+        graph.sequence(&critical),
+        critical_time
+    );
+
+    if options.gantt {
+        print!("{}", schedule.gantt(&graph, &critical));
     }
-    let nodes = requisites
-        .iter()
-        .map(|(name, req)| Node {
-            name: *name,
-            used: false,
-            requisites: req.iter().map(|a| (*a, false)).collect(),
-        })
-        .collect();
-    nodes
+
+    if let Some(path) = &options.csv {
+        fs::write(path, schedule.csv(&graph, &critical)).with_context(|| format!("Could not write {}", path))?;
+        println!("Timeline saved to {}", path);
+    }
+
+    if let Some(path) = &options.json {
+        fs::write(path, schedule.json(&graph, &critical, critical_time))
+            .with_context(|| format!("Could not write {}", path))?;
+        println!("Timeline saved to {}", path);
+    }
+
+    Ok(())
 }
@@ -1,67 +1,316 @@
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use pico_args::Arguments;
+
 const INPUT: &str = include_str!("../inputs/day_8_input");
 
+/// Numbers of a license, counting how many were read so errors can point at the spot
+#[derive(Debug)]
+struct License<I> {
+    nums: I,
+    read: usize,
+}
+
+impl<I: Iterator<Item = u32>> License<I> {
+    fn new(nums: I) -> Self {
+        Self { nums, read: 0 }
+    }
+
+    fn next(&mut self, what: &str) -> Result<u32> {
+        let num = self
+            .nums
+            .next()
+            .with_context(|| format!("License is missing {} after {} numbers", what, self.read))?;
+        self.read += 1;
+        Ok(num)
+    }
+
+    /// Checks there is nothing left after the root node
+    fn finish(self) -> Result<()> {
+        let read = self.read;
+        let trailing = self.nums.count();
+        if trailing > 0 {
+            anyhow::bail!(
+                "License has {} trailing numbers after root node ending at {}",
+                trailing,
+                read
+            );
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 struct Node {
-    child_entries: u8,
-    metadata_entries: u8,
-    children: Vec<Node>,
-    metadata: Vec<u8>,
+    /// indices of child nodes in `Tree::nodes`
+    children: Vec<usize>,
+    metadata: Vec<u32>,
+}
+
+/// Nodes kept flat in the order they appear in the license, so children always come after their parent and
+/// walking the tree needs no recursion
+#[derive(Debug)]
+struct Tree {
+    nodes: Vec<Node>,
 }
 
-impl Node {
-    fn parse(i: &mut impl Iterator<Item = u8>) -> Self {
-        let child_entries = i.next().unwrap();
-        let metadata_entries = i.next().unwrap();
+impl Tree {
+    /// Builds the tree with an explicit stack of nodes still being read
+    fn parse(license: &mut License<impl Iterator<Item = u32>>) -> Result<Self> {
+        let mut nodes = Vec::new();
+        let mut stack: Vec<(usize, OpenNode)> = Vec::new();
+
+        loop {
+            let header = OpenNode::read(license)?;
+            let index = nodes.len();
+            nodes.push(Node {
+                children: Vec::new(),
+                metadata: Vec::new(),
+            });
+            if let Some((parent, _)) = stack.last() {
+                nodes[*parent].children.push(index);
+            }
+            stack.push((index, header));
+
+            // close every node which has all children read, metadata follows them
+            while let Some((index, node)) = stack.last_mut() {
+                if node.children_left > 0 {
+                    node.children_left -= 1;
+                    break;
+                }
+
+                nodes[*index].metadata = (0..node.metadata_entries)
+                    .map(|_| license.next("node metadata"))
+                    .collect::<Result<_>>()?;
+                stack.pop();
+            }
 
-        let mut children = Vec::with_capacity(child_entries as usize);
-        for _ in 0..child_entries {
-            children.push(Node::parse(i));
+            if stack.is_empty() {
+                return Ok(Self { nodes });
+            }
         }
-        Self {
-            child_entries,
-            metadata_entries,
-            children,
-            metadata: i.take(metadata_entries as usize).collect(),
+    }
+
+    fn metadata_sum(&self) -> u64 {
+        self.nodes
+            .iter()
+            .flat_map(|node| &node.metadata)
+            .map(|a| *a as u64)
+            .sum()
+    }
+
+    /// Writes the tree back in the flat license format
+    fn encode(&self, out: &mut Vec<u32>) {
+        // nodes with how many of their children are already written
+        let mut stack = vec![(0, 0)];
+
+        while let Some((index, written)) = stack.last_mut() {
+            let node = &self.nodes[*index];
+            if *written == 0 {
+                out.push(node.children.len() as u32);
+                out.push(node.metadata.len() as u32);
+            }
+
+            match node.children.get(*written) {
+                Some(child) => {
+                    *written += 1;
+                    stack.push((*child, 0));
+                }
+                None => {
+                    out.extend_from_slice(&node.metadata);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    /// Computes value of every node, going backwards so children are always done before their parent
+    fn values(&self) -> Vec<u64> {
+        let mut values = vec![0; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate().rev() {
+            values[index] = if node.children.is_empty() {
+                node.metadata.iter().map(|a| *a as u64).sum()
+            } else {
+                node.metadata
+                    .iter()
+                    .filter_map(|data| data.checked_sub(1).and_then(|i| node.children.get(i as usize)))
+                    .map(|child| values[*child])
+                    .sum()
+            };
         }
+        values
     }
 
-    fn metadata_sum(&self) -> u32 {
-        let mut sum = self.metadata.iter().map(|a| *a as u32).sum();
+    /// Renders every node on its own line, indented by depth
+    fn pretty(&self, out: &mut String) {
+        let values = self.values();
+        let mut depths = vec![0; self.nodes.len()];
 
-        for child in &self.children {
-            sum += child.metadata_sum();
+        for (index, node) in self.nodes.iter().enumerate() {
+            for child in &node.children {
+                depths[*child] = depths[index] + 1;
+            }
+            writeln!(
+                out,
+                "{:indent$}node with {} children, metadata {:?}, value {}",
+                "",
+                node.children.len(),
+                node.metadata,
+                values[index],
+                indent = depths[index] * 2
+            )
+            .unwrap();
         }
+    }
+}
 
-        sum
+#[derive(Debug)]
+struct TreeSums {
+    metadata_sum: u64,
+    adv_sum: u64,
+}
+
+/// Node which still has children or metadata to be read
+#[derive(Debug)]
+struct OpenNode {
+    children_left: u32,
+    metadata_entries: u32,
+    child_values: Vec<u64>,
+}
+
+impl OpenNode {
+    fn read(license: &mut License<impl Iterator<Item = u32>>) -> Result<Self> {
+        let children_left = license.next("node header")?;
+        let metadata_entries = license.next("node header")?;
+        Ok(Self {
+            children_left,
+            metadata_entries,
+            child_values: Vec::new(),
+        })
     }
+}
 
-    fn adv_sum(&self) -> u32 {
-        if self.child_entries == 0 {
-            self.metadata.iter().map(|a| *a as u32).sum()
-        } else {
-            let mut sum = 0;
-            for data in &self.metadata {
-                if *data != 0 && *data <= self.child_entries {
-                    sum += self.children[*data as usize - 1].adv_sum();
-                }
+/// Computes both sums in one pass over the numbers, keeping only the nodes on the path to the current one
+fn stream_sums(license: &mut License<impl Iterator<Item = u32>>) -> Result<TreeSums> {
+    let mut metadata_sum = 0;
+    let mut stack = vec![OpenNode::read(license)?];
+
+    loop {
+        let node = stack.last_mut().unwrap();
+        if node.children_left > 0 {
+            node.children_left -= 1;
+            stack.push(OpenNode::read(license)?);
+            continue;
+        }
+
+        // all children are done, so metadata follows
+        let node = stack.pop().unwrap();
+        let mut value = 0;
+        for _ in 0..node.metadata_entries {
+            let data = license.next("node metadata")?;
+            metadata_sum += data as u64;
+
+            if node.child_values.is_empty() {
+                value += data as u64;
+            } else if let Some(child_value) = data.checked_sub(1).and_then(|i| node.child_values.get(i as usize)) {
+                // metadata pointing to no child adds nothing
+                value += child_value;
+            }
+        }
+
+        match stack.last_mut() {
+            Some(parent) => parent.child_values.push(value),
+            None => {
+                return Ok(TreeSums {
+                    metadata_sum,
+                    adv_sum: value,
+                })
             }
-            sum
         }
     }
 }
 
-pub fn solve() {
-    let mut nums = INPUT.split_whitespace().map(|a| a.parse().unwrap());
+fn parse_input() -> Result<Vec<u32>> {
+    INPUT
+        .split_whitespace()
+        .map(|a| {
+            a.parse()
+                .with_context(|| format!("License contains invalid number {}", a))
+        })
+        .collect()
+}
+
+fn license_sums() -> Result<TreeSums> {
+    let nums = parse_input()?;
+    let mut license = License::new(nums.into_iter());
+
+    let sums = stream_sums(&mut license)?;
+    license.finish()?;
+
+    Ok(sums)
+}
+
+#[derive(Debug)]
+pub struct Options {
+    tree: bool,
+}
+
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        Ok(Self {
+            tree: args.contains("--tree"),
+        })
+    }
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    solve()?;
+    solve_extra()?;
+
+    if options.tree {
+        print_tree()?;
+    }
+
+    Ok(())
+}
+
+fn solve() -> Result<()> {
+    let sums = license_sums()?;
+
+    println!("Metadata sum = {}", sums.metadata_sum);
+
+    Ok(())
+}
+
+fn solve_extra() -> Result<()> {
+    let sums = license_sums()?;
 
-    let root = Node::parse(&mut nums);
+    println!("Advanced sum = {}", sums.adv_sum);
 
-    println!("Metadata sum = {}", root.metadata_sum());
+    Ok(())
 }
 
-pub fn solve_extra() {
-    let mut nums = INPUT.split_whitespace().map(|a| a.parse().unwrap());
+fn print_tree() -> Result<()> {
+    let nums = parse_input()?;
+    let mut license = License::new(nums.iter().copied());
+
+    let tree = Tree::parse(&mut license)?;
+    license.finish()?;
+
+    let mut out = String::new();
+    tree.pretty(&mut out);
+    print!("{}", out);
+
+    let mut encoded = Vec::with_capacity(nums.len());
+    tree.encode(&mut encoded);
+    anyhow::ensure!(nums == encoded, "Encoded tree differs from the license");
 
-    let root = Node::parse(&mut nums);
+    println!(
+        "Tree of {} numbers re-encodes to the same license, metadata sum = {}",
+        encoded.len(),
+        tree.metadata_sum()
+    );
 
-    println!("Advanced sum = {}", root.adv_sum());
+    Ok(())
 }
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
-
+use anyhow::{Context, Result};
 use nom::IResult;
+use pico_args::Arguments;
 
 const INPUT: &str = include_str!("../inputs/day_9_input");
 
@@ -17,12 +17,7 @@ impl GameInput {
         use nom::combinator::map;
         use nom::sequence::tuple;
         let (i, game_input) = map(
-            tuple((
-                digit1,
-                tag(" players; last marble is worth "),
-                digit1,
-                tag(" points"),
-            )),
+            tuple((digit1, tag(" players; last marble is worth "), digit1, tag(" points"))),
             |(players, _, top_marble, _): (&str, _, _, _)| Self {
                 players: players.parse().unwrap(),
                 top_marble: top_marble.parse().unwrap(),
@@ -33,47 +28,168 @@ impl GameInput {
     }
 }
 
-pub fn solve() {
-    solve_inter(false);
+/// Circle of marbles kept as a doubly linked ring, links are indexed by marble value
+#[derive(Debug)]
+struct Circle {
+    next: Vec<u32>,
+    prev: Vec<u32>,
+    current: u32,
 }
 
-pub fn solve_extra() {
-    solve_inter(true);
+impl Circle {
+    fn new(top_marble: u32) -> Self {
+        Self {
+            next: vec![0; top_marble as usize + 1],
+            prev: vec![0; top_marble as usize + 1],
+            current: 0,
+        }
+    }
+
+    /// Places marble `clockwise` marbles after current one, new marble becomes current
+    fn insert(&mut self, marble: u32, clockwise: usize) {
+        let mut before = self.current;
+        for _ in 1..clockwise {
+            before = self.next[before as usize];
+        }
+        let after = self.next[before as usize];
+
+        self.next[before as usize] = marble;
+        self.prev[after as usize] = marble;
+        self.next[marble as usize] = after;
+        self.prev[marble as usize] = before;
+        self.current = marble;
+    }
+
+    /// Takes out marble `counter_clockwise` marbles before current one, marble after it becomes current
+    fn remove(&mut self, counter_clockwise: usize) -> u32 {
+        let mut removed = self.current;
+        for _ in 0..counter_clockwise {
+            removed = self.prev[removed as usize];
+        }
+        let (before, after) = (self.prev[removed as usize], self.next[removed as usize]);
+
+        self.next[before as usize] = after;
+        self.prev[after as usize] = before;
+        self.current = after;
+        removed
+    }
+}
+
+#[derive(Debug)]
+struct MarbleGame {
+    players: u32,
+    top_marble: u32,
+    /// marbles divisible by this are kept by the player together with a marble taken from the circle
+    scoring_multiple: u32,
+    /// how far counter-clockwise the marble taken from the circle is
+    remove_steps: usize,
+    /// how far clockwise new marbles are placed
+    place_steps: usize,
 }
 
-fn solve_inter(larger: bool) {
-    let mut game_input = GameInput::parse(INPUT).unwrap().1;
+impl MarbleGame {
+    /// Plays the game, returns score of every player in turn order
+    fn play(&self) -> Vec<u64> {
+        let mut circle = Circle::new(self.top_marble);
+        let mut scores = vec![0; self.players as usize];
 
-    if larger {
-        game_input.top_marble *= 100;
+        for stone in 1..=self.top_marble {
+            let player = ((stone - 1) % self.players) as usize;
+            if stone % self.scoring_multiple == 0 {
+                let second = circle.remove(self.remove_steps);
+                scores[player] += stone as u64 + second as u64;
+            } else {
+                circle.insert(stone, self.place_steps);
+            }
+        }
+
+        scores
     }
+}
 
-    let mut board = vec![0];
+#[derive(Debug)]
+pub struct Options {
+    scoring_multiple: u32,
+    remove_steps: usize,
+    place_steps: usize,
+    /// how many times bigger the last marble is in second part
+    multiplier: u32,
+    scoreboard: bool,
+}
 
-    let mut scores = HashMap::new();
+impl Options {
+    pub fn from_args(args: &mut Arguments) -> Result<Self> {
+        let options = Self {
+            scoring_multiple: args
+                .opt_value_from_str("--scoring-multiple")
+                .context("Did not get valid --scoring-multiple parameter value")?
+                .unwrap_or(23),
+            remove_steps: args
+                .opt_value_from_str("--remove-steps")
+                .context("Did not get valid --remove-steps parameter value")?
+                .unwrap_or(7),
+            place_steps: args
+                .opt_value_from_str("--place-steps")
+                .context("Did not get valid --place-steps parameter value")?
+                .unwrap_or(2),
+            multiplier: args
+                .opt_value_from_str("--multiplier")
+                .context("Did not get valid --multiplier parameter value")?
+                .unwrap_or(100),
+            scoreboard: args.contains("--scoreboard"),
+        };
 
-    for i in 0..game_input.players {
-        scores.insert(i + 1, 0);
+        // with every other marble scored the circle never runs out of marbles
+        if options.scoring_multiple < 2 {
+            anyhow::bail!("--scoring-multiple has to be at least 2");
+        }
+        if options.place_steps < 1 {
+            anyhow::bail!("--place-steps has to be at least 1");
+        }
+
+        Ok(options)
     }
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    solve_inter(options, 1)?;
+    solve_inter(options, options.multiplier)?;
+
+    Ok(())
+}
+
+fn solve_inter(options: &Options, multiplier: u32) -> Result<()> {
+    let game_input = GameInput::parse(INPUT).unwrap().1;
+
+    let top_marble = game_input
+        .top_marble
+        .checked_mul(multiplier)
+        .with_context(|| format!("Last marble {} x {} is too big", game_input.top_marble, multiplier))?;
+
+    let game = MarbleGame {
+        players: game_input.players,
+        top_marble,
+        scoring_multiple: options.scoring_multiple,
+        remove_steps: options.remove_steps,
+        place_steps: options.place_steps,
+    };
+
+    let mut ranking = game.play().into_iter().enumerate().collect::<Vec<_>>();
+    ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let (winner, best_score) = ranking[0];
+    println!(
+        "Best score is {} by player #{} with last marble worth {}",
+        best_score,
+        winner + 1,
+        game.top_marble
+    );
 
-    let mut active = 0;
-
-    // now play
-    for stone in 1..=game_input.top_marble {
-        let player = (stone - 1) % game_input.players + 1;
-        if stone % 23 == 0 {
-            // apply score to player on turn
-            let new_active = (active - 7 + board.len()) % board.len() as usize;
-            let second = board.remove(new_active);
-            active = new_active;
-            *scores.get_mut(&player).unwrap() += stone as u64 + second as u64;
-        } else {
-            let new_active = (active + 2) % board.len() as usize;
-            board.insert(new_active, stone);
-            active = new_active;
+    if options.scoreboard {
+        for (rank, (player, score)) in ranking.iter().enumerate() {
+            println!("{:>4}. player #{:<4} {}", rank + 1, player + 1, score);
         }
     }
 
-    let best_score = scores.iter().max_by(|a, b| a.1.cmp(&b.1)).unwrap();
-    println!("Best score is {}", best_score.1);
+    Ok(())
 }
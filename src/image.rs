@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Black and white image, `true` pixels are drawn
+#[derive(Debug, Clone)]
+pub struct Bitmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.pixels[x + y * self.width]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        self.pixels[x + y * self.width] = value;
+    }
+
+    /// Renders bitmap into lines of `on` and `off` chars
+    pub fn render(&self, on: char, off: char) -> String {
+        let mut out = String::with_capacity((self.width + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            out.extend(row.iter().map(|pixel| if *pixel { on } else { off }));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Saves bitmap as binary PBM, each pixel blown up to `scale` x `scale` square
+    pub fn save_pbm(&self, path: impl AsRef<Path>, scale: usize) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+
+        let (width, height) = (self.width * scale, self.height * scale);
+        write!(out, "P4\n{} {}\n", width, height)?;
+
+        // rows are padded to whole bytes, first pixel in the highest bit
+        let mut row = vec![0_u8; width.div_ceil(8)];
+        for y in 0..height {
+            row.iter_mut().for_each(|byte| *byte = 0);
+            for x in 0..width {
+                if self.get(x / scale, y / scale) {
+                    row[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            out.write_all(&row)?;
+        }
+        out.flush()?;
+
+        Ok(())
+    }
+}
+
+pub type Colour = [u8; 3];
+
+/// Colour image
+#[derive(Debug, Clone)]
+pub struct Pixmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Colour>,
+}
+
+impl Pixmap {
+    pub fn new(width: usize, height: usize, background: Colour) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, colour: Colour) {
+        self.pixels[x + y * self.width] = colour;
+    }
+
+    /// Saves pixmap as binary PPM, each pixel blown up to `scale` x `scale` square
+    pub fn save_ppm(&self, path: impl AsRef<Path>, scale: usize) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+
+        write!(out, "P6\n{} {}\n255\n", self.width * scale, self.height * scale)?;
+
+        for row in self.pixels.chunks(self.width.max(1)) {
+            let line = row
+                .iter()
+                .flat_map(|colour| std::iter::repeat_n(colour, scale))
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>();
+            for _ in 0..scale {
+                out.write_all(&line)?;
+            }
+        }
+        out.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Picks `index`-th colour of a palette where neighbouring indices differ in hue as much as possible
+pub fn distinct_colour(index: usize) -> Colour {
+    // stepping hue by golden angle never comes back to the same spot
+    let hue = (index as f64 * 0.618_033_988_75).fract() * 6.0;
+    let (saturation, value) = if index.is_multiple_of(2) {
+        (0.75, 0.95)
+    } else {
+        (0.9, 0.75)
+    };
+
+    let chroma = value * saturation;
+    let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+
+    let min = value - chroma;
+    [r, g, b].map(|channel| ((channel + min) * 255.0).round() as u8)
+}
@@ -5,14 +5,21 @@ use std::time::Instant;
 use crate::days::*;
 
 mod days;
+mod image;
+mod ocr;
 
 macro_rules! day {
     ($d:tt) => {
-        || {
+        Box::new(|| {
             $d::solve();
             $d::solve_extra();
-        }
+            Ok(())
+        })
     };
+    ($d:tt, $args:expr) => {{
+        let options = $d::Options::from_args(&mut $args)?;
+        Box::new(move || $d::run(&options))
+    }};
 }
 
 fn main() -> Result<()> {
@@ -22,20 +29,28 @@ fn main() -> Result<()> {
         .value_from_fn("--day", |val| val.parse::<u32>())
         .context("Did not get valid --day parameter value")?;
 
-    let task = {
+    let bench = args.contains("--bench");
+
+    let task: Box<dyn Fn() -> Result<()>> = if bench {
+        match day {
+            5 => Box::new(day_5::bench),
+            12 => Box::new(day_12::bench),
+            _ => anyhow::bail!("this day has no benchmark"),
+        }
+    } else {
         match day {
             1 => day!(day_1),
             2 => day!(day_2),
             3 => day!(day_3),
-            4 => day!(day_4),
-            5 => day!(day_5),
-            6 => day!(day_6),
-            7 => day!(day_7),
-            8 => day!(day_8),
-            9 => day!(day_9),
-            10 => day!(day_10),
-            11 => day!(day_11),
-            12 => day!(day_12),
+            4 => day!(day_4, args),
+            5 => day!(day_5, args),
+            6 => day!(day_6, args),
+            7 => day!(day_7, args),
+            8 => day!(day_8, args),
+            9 => day!(day_9, args),
+            10 => day!(day_10, args),
+            11 => day!(day_11, args),
+            12 => day!(day_12, args),
             13 => day!(day_13),
             14 => day!(day_14),
             15 => day!(day_15),
@@ -43,20 +58,22 @@ fn main() -> Result<()> {
             _ => anyhow::bail!("this day is not yet implemented"),
         }
     };
+    args.finish()?;
+
     println!("Running day {}", day);
     println!("¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯¯");
-    solver(task);
-
-    Ok(())
+    solver(task)
 }
 
-fn solver<F>(f: F)
+fn solver<F>(f: F) -> Result<()>
 where
-    F: Fn(),
+    F: Fn() -> Result<()>,
 {
     let start = Instant::now();
-    f();
+    f()?;
     let time = start.elapsed();
     println!("__________________________________________");
     println!("Time it took: {:03} seconds.", time.as_secs_f32());
+
+    Ok(())
 }
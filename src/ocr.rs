@@ -0,0 +1,164 @@
+use anyhow::Result;
+
+use crate::image::Bitmap;
+
+const GLYPH_WIDTH: usize = 6;
+const GLYPH_HEIGHT: usize = 10;
+
+/// Letters of the 6x10 font used in puzzle outputs
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT]); 15] = [
+    (
+        'A',
+        [
+            "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#",
+        ],
+    ),
+    (
+        'B',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####.",
+        ],
+    ),
+    (
+        'C',
+        [
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####.",
+        ],
+    ),
+    (
+        'E',
+        [
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######",
+        ],
+    ),
+    (
+        'F',
+        [
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#.....",
+        ],
+    ),
+    (
+        'G',
+        [
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#",
+        ],
+    ),
+    (
+        'H',
+        [
+            "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#",
+        ],
+    ),
+    (
+        'J',
+        [
+            "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###..",
+        ],
+    ),
+    (
+        'K',
+        [
+            "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#",
+        ],
+    ),
+    (
+        'L',
+        [
+            "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######",
+        ],
+    ),
+    (
+        'N',
+        [
+            "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#",
+        ],
+    ),
+    (
+        'P',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#.....",
+        ],
+    ),
+    (
+        'R',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#",
+        ],
+    ),
+    (
+        'X',
+        [
+            "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#",
+        ],
+    ),
+    (
+        'Z',
+        [
+            "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######",
+        ],
+    ),
+];
+
+/// Reads text drawn in the 6x10 font, letters have to be separated by at least one empty column
+pub fn read_text(bitmap: &Bitmap) -> Result<String> {
+    if bitmap.height() != GLYPH_HEIGHT {
+        anyhow::bail!("Text has to be {} pixels tall, got {}", GLYPH_HEIGHT, bitmap.height());
+    }
+
+    let column_empty = |x| (0..bitmap.height()).all(|y| !bitmap.get(x, y));
+
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+
+    let mut x = 0;
+    while x < bitmap.width() {
+        if column_empty(x) {
+            x += 1;
+            continue;
+        }
+
+        let start = x;
+        while x < bitmap.width() && !column_empty(x) {
+            x += 1;
+        }
+
+        match recognize_glyph(bitmap, start, x - start) {
+            Some(letter) => text.push(letter),
+            None => {
+                text.push('?');
+                unknown.push((text.len(), start, x - start));
+            }
+        }
+    }
+
+    if !unknown.is_empty() {
+        let mut message = format!("Could not recognize {} glyphs in \"{}\":", unknown.len(), text);
+        for (position, start, width) in unknown {
+            message.push_str(&format!("\nglyph {} at column {}:\n", position, start));
+            for y in 0..bitmap.height() {
+                message.extend((start..(start + width)).map(|x| if bitmap.get(x, y) { '#' } else { '.' }));
+                message.push('\n');
+            }
+        }
+        anyhow::bail!(message.trim_end().to_owned());
+    }
+
+    Ok(text)
+}
+
+fn recognize_glyph(bitmap: &Bitmap, start: usize, width: usize) -> Option<char> {
+    if width != GLYPH_WIDTH {
+        return None;
+    }
+
+    GLYPHS
+        .iter()
+        .find(|(_, rows)| {
+            rows.iter().enumerate().all(|(y, row)| {
+                row.bytes()
+                    .enumerate()
+                    .all(|(x, pixel)| bitmap.get(start + x, y) == (pixel == b'#'))
+            })
+        })
+        .map(|(letter, _)| *letter)
+}